@@ -5,7 +5,7 @@ use futures::AsyncReadExt;
 use http::Uri;
 use isahc::HttpClient;
 
-use crate::model::{ChartData, CompanyData, OptionsHeader, Response, ResponseType};
+use crate::model::{self, ChartData, CompanyData, OptionsHeader, Response, ResponseType, SearchQuote};
 use crate::{Interval, Range};
 
 /*
@@ -18,6 +18,26 @@ use crate::{Interval, Range};
  * 'upgradeDowngradeHistory', 'fundProfile', 'topHoldings', 'fundPerformance'
 */
 
+/// Yahoo has no data for the symbol, as opposed to failing to answer.
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No data found for {}", self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// Turns an error Yahoo reported into `NotFound` when that's what it means.
+fn status_error(symbol: &str, what: &str, err: model::Error) -> anyhow::Error {
+    if err.code == "Not Found" {
+        return NotFound(symbol.to_string()).into();
+    }
+    anyhow::anyhow!("Error getting {} for {}: {}", what, symbol, err.description)
+}
+
 #[derive(Debug)]
 pub struct Client {
     client: HttpClient,
@@ -67,6 +87,31 @@ impl Client {
         interval: Interval,
         range: Range,
         include_pre_post: bool,
+    ) -> Result<ChartData> {
+        self.chart_data(symbol, interval, range, include_pre_post, false)
+            .await
+    }
+
+    /// Same as `get_chart_data`, plus the dividends, splits and earnings
+    /// dates that fall within the range.
+    pub async fn get_chart_data_with_events(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        range: Range,
+        include_pre_post: bool,
+    ) -> Result<ChartData> {
+        self.chart_data(symbol, interval, range, include_pre_post, true)
+            .await
+    }
+
+    async fn chart_data(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        range: Range,
+        include_pre_post: bool,
+        include_events: bool,
     ) -> Result<ChartData> {
         let mut params = HashMap::new();
         params.insert("interval", format!("{}", interval));
@@ -76,6 +121,10 @@ impl Client {
             params.insert("includePrePost", format!("{}", true));
         }
 
+        if include_events {
+            params.insert("events", "div,splits,earn".to_string());
+        }
+
         let url = self.get_url(
             Version::V8,
             &format!("finance/chart/{}", symbol),
@@ -88,11 +137,7 @@ impl Client {
 
         if let Response::Chart(response) = _response {
             if let Some(err) = response.chart.error {
-                bail!(
-                    "Error getting chart data for {}: {}",
-                    symbol,
-                    err.description
-                );
+                return Err(status_error(symbol, "chart data", err));
             }
 
             if let Some(mut result) = response.chart.result {
@@ -106,7 +151,7 @@ impl Client {
 
     pub async fn get_company_data(&self, symbol: &str) -> Result<CompanyData> {
         let mut params = HashMap::new();
-        params.insert("modules", "price,assetProfile".to_string());
+        params.insert("modules", "price,assetProfile,calendarEvents,summaryDetail".to_string());
 
         let url = self.get_url(
             Version::V10,
@@ -119,11 +164,7 @@ impl Client {
 
         if let Response::Company(response) = _response {
             if let Some(err) = response.company.error {
-                bail!(
-                    "Error getting company data for {}: {}",
-                    symbol,
-                    err.description
-                );
+                return Err(status_error(symbol, "company data", err));
             }
 
             if let Some(mut result) = response.company.result {
@@ -196,6 +237,22 @@ impl Client {
         }
         bail!("Failed to get options data for {}", symbol);
     }
+
+    /// Symbols whose ticker or name match `query`, best match first.
+    pub async fn search(&self, query: &str, count: usize) -> Result<Vec<SearchQuote>> {
+        let mut params = HashMap::new();
+        params.insert("q", query.to_string());
+        params.insert("quotesCount", format!("{}", count));
+        params.insert("newsCount", format!("{}", 0));
+
+        let url = self.get_url(Version::V1, "finance/search", Some(params))?;
+        let response_type = ResponseType::Search;
+
+        if let Response::Search(response) = self.get(url, response_type).await? {
+            return Ok(response.quotes);
+        }
+        bail!("Failed to search for {}", query);
+    }
 }
 
 impl Default for Client {
@@ -210,6 +267,7 @@ impl Default for Client {
 
 #[derive(Debug, Clone)]
 pub enum Version {
+    V1,
     V7,
     V8,
     V10,
@@ -218,6 +276,7 @@ pub enum Version {
 impl Version {
     fn as_str(&self) -> &'static str {
         match self {
+            Version::V1 => "v1",
             Version::V7 => "v7",
             Version::V8 => "v8",
             Version::V10 => "v10",
@@ -274,6 +333,14 @@ mod tests {
         }
     }
 
+    #[async_std::test]
+    async fn test_search() {
+        let client = Client::new();
+
+        let quotes = client.search("apple", 5).await.unwrap();
+        assert!(quotes.iter().any(|q| q.symbol == "AAPL"));
+    }
+
     #[async_std::test]
     async fn test_chart_data() {
         let client = Client::new();
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::marker::PhantomData;
 
@@ -9,12 +10,14 @@ pub(crate) enum ResponseType {
     Chart,
     Company,
     Options,
+    Search,
 }
 
 pub(crate) enum Response {
     Chart(Chart),
     Company(Company),
     Options(Options),
+    Search(Search),
 }
 
 impl ResponseType {
@@ -32,6 +35,10 @@ impl ResponseType {
                 Ok(deser) => Ok(Response::Options(deser)),
                 Err(e) => bail!(e),
             },
+            ResponseType::Search => match serde_json::from_slice(body) {
+                Ok(deser) => Ok(Response::Search(deser)),
+                Err(e) => bail!(e),
+            },
         }
     }
 }
@@ -62,6 +69,7 @@ pub struct ChartData {
     pub meta: ChartMeta,
     pub timestamp: Vec<i64>,
     pub indicators: ChartIndicators,
+    pub events: Option<ChartEvents>,
 }
 
 #[serde(rename_all = "camelCase")]
@@ -87,6 +95,40 @@ pub struct ChartTradingPeriod {
     pub start: i64,
     pub end: i64,
 }
+/// Corporate actions within the chart's range, each keyed by its timestamp.
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChartEvents {
+    #[serde(default)]
+    pub dividends: HashMap<String, ChartDividend>,
+    #[serde(default)]
+    pub splits: HashMap<String, ChartSplit>,
+    #[serde(default)]
+    pub earnings: HashMap<String, ChartEarnings>,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChartDividend {
+    pub amount: f64,
+    pub date: i64,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChartSplit {
+    pub date: i64,
+    pub numerator: f64,
+    pub denominator: f64,
+    pub split_ratio: Option<String>,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct ChartEarnings {
+    pub date: i64,
+}
+
 #[serde(rename_all = "camelCase")]
 #[derive(Debug, Deserialize, Clone)]
 pub struct ChartIndicators {
@@ -136,6 +178,8 @@ pub struct CompanyData {
     #[serde(rename = "assetProfile")]
     pub profile: Option<CompanyProfile>,
     pub price: CompanyPrice,
+    pub calendar_events: Option<CompanyCalendarEvents>,
+    pub summary_detail: Option<CompanySummaryDetail>,
 }
 
 #[serde(rename_all = "camelCase")]
@@ -161,7 +205,8 @@ pub struct CompanyPrice {
     pub post_market_price: CompanyPostMarketPrice,
     pub regular_market_volume: CompanyMarketPrice,
     pub currency: Option<String>,
-    pub market_cap: Option<CompanyMarketCap>
+    pub market_cap: Option<CompanyMarketCap>,
+    pub quote_type: Option<String>,
 }
 
 #[serde(rename_all = "camelCase")]
@@ -187,6 +232,34 @@ pub struct CompanyPostMarketPrice {
     pub price: Option<f64>,
 }
 
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompanySummaryDetail {
+    pub open_interest: Option<CompanyMarketCap>,
+    pub expire_date: Option<CompanyDate>,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompanyCalendarEvents {
+    pub earnings: Option<CompanyEarningsCalendar>,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompanyEarningsCalendar {
+    #[serde(default)]
+    pub earnings_date: Vec<CompanyDate>,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct CompanyDate {
+    #[serde(rename = "raw")]
+    pub date: i64,
+    pub fmt: Option<String>,
+}
+
 #[serde(rename_all = "camelCase")]
 #[derive(Debug, Deserialize, Clone)]
 pub struct Options {
@@ -239,6 +312,24 @@ pub struct OptionsContract {
     pub currency: Option<String>,
 }
 
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct Search {
+    #[serde(default)]
+    pub quotes: Vec<SearchQuote>,
+}
+
+#[serde(rename_all = "camelCase")]
+#[derive(Debug, Deserialize, Clone)]
+pub struct SearchQuote {
+    pub symbol: String,
+    pub shortname: Option<String>,
+    pub longname: Option<String>,
+    pub quote_type: Option<String>,
+    #[serde(rename = "exchDisp")]
+    pub exchange: Option<String>,
+}
+
 fn deserialize_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
 where
     D: Deserializer<'de>,
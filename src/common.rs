@@ -6,7 +6,7 @@ use chrono::{Local, TimeZone, Utc};
 use itertools::izip;
 use stout_api::Interval;
 
-use crate::api::model::ChartData;
+use crate::api::model::{ChartCurrentTradingPeriod, ChartData};
 use crate::api::Range;
 
 #[derive(Clone, Copy, PartialOrd, Debug, Hash, PartialEq, Eq)]
@@ -40,6 +40,18 @@ impl FromStr for TimeFrame {
 }
 
 impl TimeFrame {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimeFrame::Day1 => "1D",
+            TimeFrame::Week1 => "1W",
+            TimeFrame::Month1 => "1M",
+            TimeFrame::Month3 => "3M",
+            TimeFrame::Month6 => "6M",
+            TimeFrame::Year1 => "1Y",
+            TimeFrame::Year5 => "5Y",
+        }
+    }
+
     pub fn idx(self) -> usize {
         match self {
             TimeFrame::Day1 => 0,
@@ -62,6 +74,12 @@ impl TimeFrame {
         TimeFrame::Year5,
     ];
 
+    /// Whether charts over the time frame mark earnings, dividends and
+    /// splits. A single session rarely has any worth the extra data.
+    pub fn shows_events(self) -> bool {
+        self != TimeFrame::Day1
+    }
+
     pub fn update_interval(self) -> Duration {
         match self {
             TimeFrame::Day1 => Duration::from_secs(60),
@@ -155,6 +173,14 @@ impl Default for MarketHours {
     }
 }
 
+impl MarketHours {
+    /// Number of the trading day `timestamp` falls in. Days roll over well
+    /// before pre market opens, so post market stays with its own session.
+    pub fn trading_day(&self, timestamp: i64) -> i64 {
+        (timestamp - self.0 + 8 * 60 * 60).div_euclid(24 * 60 * 60)
+    }
+}
+
 impl Iterator for MarketHours {
     type Item = i64;
 
@@ -179,6 +205,26 @@ pub enum TradingPeriod {
     Post,
 }
 
+impl TradingPeriod {
+    /// Which session of `periods` the `timestamp` falls in, if any.
+    pub fn of(timestamp: i64, periods: &ChartCurrentTradingPeriod) -> Option<TradingPeriod> {
+        let sessions = [
+            (TradingPeriod::Pre, &periods.pre),
+            (TradingPeriod::Regular, &periods.regular),
+            (TradingPeriod::Post, &periods.post),
+        ];
+
+        sessions
+            .iter()
+            .find(|(_, p)| p.start <= timestamp && timestamp < p.end)
+            .map(|(period, _)| *period)
+    }
+
+    pub fn is_extended(self) -> bool {
+        self != TradingPeriod::Regular
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Price {
     pub close: f64,
@@ -0,0 +1,207 @@
+use crate::common::{chart_data_to_prices, Price, TimeFrame};
+use futures::future::join_all;
+use std::collections::BTreeMap;
+
+/// One color per symbol, so comparisons are capped at the overlay palette.
+pub const MAX_SYMBOLS: usize = 5;
+
+pub const USAGE: &str = "Usage: `~compare $AAPL $MSFT $QQQ [1D|1W|1M|3M|6M|1Y|5Y]`";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareRequest {
+    pub symbols: Vec<String>,
+    pub time_frame: TimeFrame,
+}
+
+/// Parses `$AAPL $MSFT 1Y` style arguments. The time frame defaults to a day
+/// and may appear anywhere.
+pub fn parse_request(tokens: &[&str]) -> Result<CompareRequest, String> {
+    let mut symbols: Vec<String> = vec![];
+    let mut time_frame = TimeFrame::Day1;
+
+    for token in tokens {
+        if let Ok(t) = token.parse() {
+            time_frame = t;
+            continue;
+        }
+
+        let symbol = token.trim_start_matches('$').to_uppercase();
+        if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric() || ".-=^".contains(c)) {
+            return Err(format!("Didn't understand `{}`", token));
+        }
+        let symbol = crate::symbols::resolve(&symbol);
+        if !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+
+    if symbols.len() < 2 {
+        return Err(USAGE.to_string());
+    }
+    if symbols.len() > MAX_SYMBOLS {
+        return Err(format!("Can only compare up to {} symbols at once", MAX_SYMBOLS));
+    }
+
+    Ok(CompareRequest { symbols, time_frame })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub time_frame: TimeFrame,
+    /// Timestamps at which every symbol has a close.
+    pub dates: Vec<i64>,
+    /// Each symbol's change from the first date as a fraction, one per date.
+    pub series: Vec<(String, Vec<f64>)>,
+}
+
+impl Comparison {
+    /// Aligns the price series on the bars they have in common and normalizes
+    /// each to its change from the first. Bars are matched within the time
+    /// frame's interval, so symbols that trade on different clocks still line
+    /// up. Returns `None` if fewer than two bars are shared.
+    pub fn new(time_frame: TimeFrame, series: &[(String, Vec<Price>)]) -> Option<Comparison> {
+        let bucket = |date: i64| date - date.rem_euclid(time_frame.round_by());
+        let closes = series
+            .iter()
+            .map(|(_, prices)| {
+                let mut closes = BTreeMap::new();
+                for p in prices.iter().filter(|p| p.close > 0.0) {
+                    closes.entry(bucket(p.date)).or_insert((p.date, p.close));
+                }
+                closes
+            })
+            .collect::<Vec<BTreeMap<i64, (i64, f64)>>>();
+
+        let (first, rest) = closes.split_first()?;
+        let shared = first
+            .iter()
+            .filter(|(key, _)| rest.iter().all(|c| c.contains_key(key)))
+            .collect::<Vec<(&i64, &(i64, f64))>>();
+
+        if shared.len() < 2 {
+            return None;
+        }
+
+        let dates = shared.iter().map(|(_, (date, _))| *date).collect();
+        let series = series
+            .iter()
+            .zip(&closes)
+            .map(|((symbol, _), closes)| {
+                let values = shared.iter().map(|(key, _)| closes[key].1).collect::<Vec<f64>>();
+                (symbol.clone(), normalize(&values))
+            })
+            .collect();
+
+        Some(Comparison {
+            time_frame,
+            dates,
+            series,
+        })
+    }
+
+    /// Each symbol's change over the whole period.
+    pub fn final_returns(&self) -> Vec<(&str, f64)> {
+        self.series
+            .iter()
+            .map(|(symbol, values)| (symbol.as_str(), values.last().copied().unwrap_or(0.0)))
+            .collect()
+    }
+}
+
+/// Change of each value from the first, as a fraction.
+pub fn normalize(values: &[f64]) -> Vec<f64> {
+    match values.first() {
+        Some(first) => values.iter().map(|v| v / first - 1.0).collect(),
+        None => vec![],
+    }
+}
+
+async fn fetch_prices(symbol: &str, time_frame: TimeFrame) -> Result<Vec<Price>, String> {
+    crate::CLIENT
+        .get_chart_data(symbol, time_frame.api_interval(), time_frame.as_range(), false)
+        .await
+        .map(chart_data_to_prices)
+        .map_err(|why| {
+            println!("Error fetching chart data for {}: {:?}", symbol, why);
+            format!("Couldn't get chart data for ${}", symbol)
+        })
+}
+
+/// Fetches every symbol over the same time frame and lines them up.
+pub async fn fetch_comparison(request: &CompareRequest) -> Result<Comparison, String> {
+    let fetches = request
+        .symbols
+        .iter()
+        .map(|symbol| fetch_prices(symbol, request.time_frame));
+
+    let series = request
+        .symbols
+        .iter()
+        .cloned()
+        .zip(join_all(fetches).await)
+        .map(|(symbol, prices)| prices.map(|prices| (symbol, prices)))
+        .collect::<Result<Vec<(String, Vec<Price>)>, String>>()?;
+
+    Comparison::new(request.time_frame, &series)
+        .ok_or_else(|| "Not enough overlapping price data to compare".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(bars: &[(i64, f64)]) -> Vec<Price> {
+        bars.iter()
+            .map(|(date, close)| Price {
+                close: *close,
+                date: *date,
+                ..Price::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_parses_compare_requests() {
+        let request = parse_request(&["$AAPL", "msft", "1Y", "$QQQ", "$AAPL"]).unwrap();
+        assert_eq!(request.symbols, ["AAPL", "MSFT", "QQQ"]);
+        assert_eq!(request.time_frame, TimeFrame::Year1);
+
+        assert_eq!(parse_request(&["$AAPL", "$BTC-USD"]).unwrap().time_frame, TimeFrame::Day1);
+        assert!(parse_request(&["$AAPL"]).is_err());
+        assert!(parse_request(&["$AAPL", "$MSFT", "huh?"]).is_err());
+        assert!(parse_request(&["$A", "$B", "$C", "$D", "$E", "$F"]).is_err());
+    }
+
+    #[test]
+    fn it_aligns_and_normalizes_series() {
+        let series = vec![
+            ("AAPL".to_string(), prices(&[(60, 100.0), (120, 0.0), (180, 110.0), (240, 120.0)])),
+            ("MSFT".to_string(), prices(&[(0, 50.0), (60, 40.0), (120, 45.0), (180, 50.0), (240, 60.0)])),
+        ];
+
+        let comparison = Comparison::new(TimeFrame::Day1, &series).unwrap();
+        assert_eq!(comparison.dates, [60, 180, 240]);
+        assert_eq!(comparison.series[1].1, [0.0, 0.25, 0.5]);
+        let returns = comparison.final_returns();
+        assert_eq!(returns[0].0, "AAPL");
+        assert!((returns[0].1 - 0.2).abs() < 1e-9);
+        assert_eq!(returns[1], ("MSFT", 0.5));
+
+        let disjoint = vec![series[0].clone(), ("QQQ".to_string(), prices(&[(300, 1.0), (360, 2.0)]))];
+        assert_eq!(Comparison::new(TimeFrame::Day1, &disjoint), None);
+    }
+
+    #[test]
+    fn it_matches_bars_within_an_interval() {
+        // Daily equity bars open mid-session while crypto bars start at midnight.
+        let day = 24 * 60 * 60;
+        let series = vec![
+            ("SPY".to_string(), prices(&[(52200, 400.0), (day + 52200, 404.0)])),
+            ("BTC-USD".to_string(), prices(&[(0, 50_000.0), (day, 37_500.0)])),
+        ];
+
+        let comparison = Comparison::new(TimeFrame::Year1, &series).unwrap();
+        assert_eq!(comparison.dates, [52200, day + 52200]);
+        assert_eq!(comparison.series[1].1, [0.0, -0.25]);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::api::model::ChartEvents;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EventKind {
+    Earnings,
+    Dividend,
+    Split,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 3] = [EventKind::Earnings, EventKind::Dividend, EventKind::Split];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EventKind::Earnings => "Earnings",
+            EventKind::Dividend => "Dividend",
+            EventKind::Split => "Split",
+        }
+    }
+
+    /// The letter drawn inside the event's marker.
+    pub fn letter(self) -> &'static str {
+        match self {
+            EventKind::Earnings => "E",
+            EventKind::Dividend => "D",
+            EventKind::Split => "S",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    pub date: i64,
+    pub kind: EventKind,
+}
+
+/// Flattens the chart's corporate actions into one list, oldest first.
+pub fn chart_events(events: &ChartEvents) -> Vec<Event> {
+    let earnings = events.earnings.values().map(|e| Event {
+        date: e.date,
+        kind: EventKind::Earnings,
+    });
+    let dividends = events.dividends.values().map(|d| Event {
+        date: d.date,
+        kind: EventKind::Dividend,
+    });
+    let splits = events.splits.values().map(|s| Event {
+        date: s.date,
+        kind: EventKind::Split,
+    });
+
+    let mut events = earnings.chain(dividends).chain(splits).collect::<Vec<Event>>();
+    events.sort_by_key(|e| (e.date, e.kind));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_flattens_chart_events() {
+        let events: ChartEvents = serde_json::from_str(
+            r#"{
+                "dividends": {
+                    "1612535400": {"amount": 0.205, "date": 1612535400},
+                    "1604673000": {"amount": 0.205, "date": 1604673000}
+                },
+                "splits": {
+                    "1598880600": {"date": 1598880600, "numerator": 4, "denominator": 1, "splitRatio": "4:1"}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let events = chart_events(&events);
+        assert_eq!(
+            events.iter().map(|e| (e.date, e.kind)).collect::<Vec<(i64, EventKind)>>(),
+            [
+                (1598880600, EventKind::Split),
+                (1604673000, EventKind::Dividend),
+                (1612535400, EventKind::Dividend),
+            ]
+        );
+    }
+}
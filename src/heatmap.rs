@@ -0,0 +1,466 @@
+use crate::plot;
+use crate::stock;
+use crate::theme::Theme;
+use chrono::Utc;
+use futures::stream::{self, StreamExt};
+use serenity::builder::{CreateAttachment, CreateEmbed, CreateEmbedFooter, CreateMessage};
+use serenity::http::Http;
+use serenity::model::id::ChannelId;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_UNIVERSE: &str = "megacap";
+
+/// The largest S&P 500 names across every sector, small enough to fetch on demand.
+const MEGACAP: &[&str] = &[
+    "AAPL", "MSFT", "NVDA", "ADBE", "CRM", "INTC", "CSCO", "ORCL", "AVGO", "GOOGL", "FB", "NFLX", "DIS", "VZ",
+    "T", "CMCSA", "AMZN", "TSLA", "HD", "NKE", "MCD", "SBUX", "WMT", "PG", "KO", "PEP", "COST", "JNJ", "UNH",
+    "PFE", "MRK", "ABBV", "TMO", "BRK-B", "JPM", "BAC", "V", "MA", "WFC", "BA", "HON", "UNP", "CAT", "XOM",
+    "CVX", "NEE", "DUK", "AMT", "PLD", "LIN",
+];
+
+/// Wait a few minutes past the close so the day's final prices are in.
+const CLOSE_DELAY_SECS: i64 = 10 * 60;
+/// How often to look for the next session when there's nothing to post.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How many symbols are fetched at once.
+const MAX_CONCURRENT_FETCHES: usize = 8;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Universe {
+    pub name: String,
+    pub symbols: Vec<String>,
+}
+
+/// Reads universes written as `name=SYM,SYM;name=SYM,...`, skipping any
+/// without a name or symbols.
+pub fn parse_universes(s: &str) -> Vec<Universe> {
+    s.split(';')
+        .filter_map(|entry| {
+            let (name, symbols) = entry.split_once('=')?;
+            let name = name.trim().to_lowercase();
+            let symbols = symbols
+                .split(',')
+                .map(|s| s.trim().trim_start_matches('$').to_uppercase())
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<String>>();
+
+            if name.is_empty() || symbols.is_empty() {
+                None
+            } else {
+                Some(Universe { name, symbols })
+            }
+        })
+        .collect()
+}
+
+/// The built-in universe followed by any set in `HEATMAP_UNIVERSES`, which
+/// may also replace it.
+pub fn universes() -> Vec<Universe> {
+    let configured = env::var("HEATMAP_UNIVERSES")
+        .map(|s| parse_universes(&s))
+        .unwrap_or_default();
+
+    let megacap = Universe {
+        name: DEFAULT_UNIVERSE.to_string(),
+        symbols: MEGACAP.iter().map(|s| s.to_string()).collect(),
+    };
+
+    let replaced = configured.iter().any(|u| u.name == megacap.name);
+    std::iter::once(megacap)
+        .filter(|_| !replaced)
+        .chain(configured)
+        .collect()
+}
+
+pub fn find_universe(name: Option<&str>) -> Option<Universe> {
+    let name = name.unwrap_or(DEFAULT_UNIVERSE).to_lowercase();
+    universes().into_iter().find(|u| u.name == name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    pub symbol: String,
+    pub sector: String,
+    pub market_cap: f64,
+    /// Change since the previous close, as a fraction.
+    pub change: f64,
+}
+
+async fn fetch_tile(symbol: &str) -> Option<Tile> {
+    let data = match crate::CLIENT.get_company_data(symbol).await {
+        Ok(data) => data,
+        Err(why) => {
+            println!("Error fetching heatmap data for {}: {:?}", symbol, why);
+            return None;
+        }
+    };
+
+    let market_cap = data.price.market_cap.and_then(|c| c.amount).filter(|c| *c > 0.0)?;
+    let previous_close = data.price.regular_market_previous_close.price;
+    if previous_close <= 0.0 {
+        return None;
+    }
+
+    Some(Tile {
+        symbol: symbol.to_string(),
+        sector: data
+            .profile
+            .and_then(|p| p.sector)
+            .unwrap_or_else(|| "Other".to_string()),
+        market_cap,
+        change: data.price.regular_market_price.price / previous_close - 1.0,
+    })
+}
+
+/// Fetches every symbol in the universe a few at a time, leaving out any
+/// that fail.
+pub async fn fetch_tiles(universe: &Universe) -> Vec<Tile> {
+    stream::iter(universe.symbols.clone())
+        .map(|symbol| async move { fetch_tile(&symbol).await })
+        .buffer_unordered(MAX_CONCURRENT_FETCHES)
+        .collect::<Vec<Option<Tile>>>()
+        .await
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    pub fn area(&self) -> f64 {
+        self.width * self.height
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sector {
+    pub name: String,
+    pub bounds: Rect,
+    /// Where the sector's name goes, if there's room for it.
+    pub header: Option<Rect>,
+    pub tiles: Vec<(Tile, Rect)>,
+}
+
+/// Groups the tiles by sector and lays both levels out as a treemap, largest
+/// first, with each area proportional to market cap.
+pub fn layout(tiles: &[Tile], bounds: Rect, header_height: f64) -> Vec<Sector> {
+    let mut sectors: Vec<(String, Vec<Tile>)> = vec![];
+    for tile in tiles {
+        match sectors.iter_mut().find(|(name, _)| *name == tile.sector) {
+            Some((_, tiles)) => tiles.push(tile.clone()),
+            None => sectors.push((tile.sector.clone(), vec![tile.clone()])),
+        }
+    }
+
+    let total = |tiles: &[Tile]| tiles.iter().map(|t| t.market_cap).sum::<f64>();
+    for (_, tiles) in sectors.iter_mut() {
+        tiles.sort_by(|a, b| b.market_cap.partial_cmp(&a.market_cap).unwrap());
+    }
+    sectors.sort_by(|a, b| total(&b.1).partial_cmp(&total(&a.1)).unwrap());
+
+    let weights = sectors.iter().map(|(_, tiles)| total(tiles)).collect::<Vec<f64>>();
+    sectors
+        .into_iter()
+        .zip(squarify(&weights, bounds))
+        .map(|((name, tiles), bounds)| {
+            let has_header = bounds.height >= header_height * 3.0 && bounds.width >= header_height * 4.0;
+            let (header, body) = if has_header {
+                let header = Rect {
+                    height: header_height,
+                    ..bounds
+                };
+                let body = Rect {
+                    y: bounds.y + header_height,
+                    height: bounds.height - header_height,
+                    ..bounds
+                };
+                (Some(header), body)
+            } else {
+                (None, bounds)
+            };
+
+            let weights = tiles.iter().map(|t| t.market_cap).collect::<Vec<f64>>();
+            let rects = squarify(&weights, body);
+            Sector {
+                name,
+                bounds,
+                header,
+                tiles: tiles.into_iter().zip(rects).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Splits `bounds` into one rectangle per weight, in order, keeping them as
+/// close to square as it can (Bruls, Huizing and van Wijk's squarified
+/// treemap). Weights should be positive and sorted largest first.
+pub fn squarify(weights: &[f64], bounds: Rect) -> Vec<Rect> {
+    let total = weights.iter().sum::<f64>();
+    if total <= 0.0 || bounds.area() <= 0.0 {
+        return weights.iter().map(|_| Rect { width: 0.0, height: 0.0, ..bounds }).collect();
+    }
+
+    let areas = weights.iter().map(|w| w / total * bounds.area()).collect::<Vec<f64>>();
+    let mut rects = vec![];
+    let mut rest = bounds;
+    let mut start = 0;
+
+    while start < areas.len() {
+        let side = rest.width.min(rest.height);
+        let mut end = start + 1;
+        while end < areas.len() && worst_ratio(&areas[start..=end], side) <= worst_ratio(&areas[start..end], side) {
+            end += 1;
+        }
+
+        let row = &areas[start..end];
+        let row_area = row.iter().sum::<f64>();
+        if rest.width >= rest.height {
+            // Fill a column down the left of what's left.
+            let width = row_area / rest.height;
+            let mut y = rest.y;
+            for area in row {
+                let height = area / width;
+                rects.push(Rect { x: rest.x, y, width, height });
+                y += height;
+            }
+            rest = Rect {
+                x: rest.x + width,
+                width: rest.width - width,
+                ..rest
+            };
+        } else {
+            // Fill a row along the top.
+            let height = row_area / rest.width;
+            let mut x = rest.x;
+            for area in row {
+                let width = area / height;
+                rects.push(Rect { x, y: rest.y, width, height });
+                x += width;
+            }
+            rest = Rect {
+                y: rest.y + height,
+                height: rest.height - height,
+                ..rest
+            };
+        }
+
+        start = end;
+    }
+
+    rects
+}
+
+/// The most elongated aspect ratio among `row` if laid along a side of `side`.
+fn worst_ratio(row: &[f64], side: f64) -> f64 {
+    let sum = row.iter().sum::<f64>();
+    let max = row.iter().copied().fold(f64::MIN, f64::max);
+    let min = row.iter().copied().fold(f64::MAX, f64::min);
+
+    (side * side * max / (sum * sum)).max(sum * sum / (side * side * min))
+}
+
+/// Fetches and draws the universe, then posts it to `channel_id`.
+pub async fn post(http: &Http, channel_id: ChannelId, universe: &Universe, theme: Theme) -> Result<(), String> {
+    let tiles = fetch_tiles(universe).await;
+    if tiles.is_empty() {
+        return Err(format!("Couldn't get market data for `{}`", universe.name));
+    }
+
+    let image = plot::build_heatmap(&tiles, theme).map_err(|why| {
+        println!("Error rendering heatmap for {}: {:?}", universe.name, why);
+        "Couldn't draw the heatmap".to_string()
+    })?;
+
+    let filename = "heatmap.png";
+    let embed = CreateEmbed::new()
+        .title(format!("Heatmap - {}", universe.name))
+        .attachment(filename)
+        .footer(CreateEmbedFooter::new("Sized by market cap, colored by change since the previous close"));
+    channel_id
+        .send_message(http, CreateMessage::new().embed(embed).add_file(CreateAttachment::bytes(image, filename)))
+        .await
+        .map(|_| ())
+        .map_err(|why| {
+            println!("Error sending heatmap: {:?}", why);
+            "Couldn't send the heatmap".to_string()
+        })
+}
+
+#[derive(Debug, Clone)]
+pub struct HeatmapSchedule {
+    pub channel_id: u64,
+    pub universe: String,
+}
+
+impl HeatmapSchedule {
+    /// Reads the daily post's configuration from the environment. It's
+    /// disabled unless `HEATMAP_CHANNEL_ID` is set; `HEATMAP_UNIVERSE` picks
+    /// what to draw.
+    pub fn from_env() -> Option<HeatmapSchedule> {
+        Some(HeatmapSchedule {
+            channel_id: env::var("HEATMAP_CHANNEL_ID").ok()?.parse().ok()?,
+            universe: env::var("HEATMAP_UNIVERSE").unwrap_or_else(|_| DEFAULT_UNIVERSE.to_string()),
+        })
+    }
+}
+
+/// How many seconds from `now` the heatmap for the session closing at
+/// `close` is due, or `None` once it's been posted or its time has passed.
+pub fn post_delay(close: i64, last_posted: Option<i64>, now: i64) -> Option<i64> {
+    let post_at = close + CLOSE_DELAY_SECS;
+    if last_posted == Some(close) || now > post_at {
+        None
+    } else {
+        Some(post_at - now)
+    }
+}
+
+/// Starts posting the heatmap after every market close, going by the
+/// exchange's own session, so holidays and early closes are respected. Only
+/// the first call has any effect, so this is safe to call from `ready`.
+pub fn spawn(http: Arc<Http>, schedule: HeatmapSchedule) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut last_posted = None;
+
+        loop {
+            let close = match stock::market_session().await {
+                Ok(session) => session.end,
+                Err(why) => {
+                    println!("Error checking market hours for the heatmap: {}", why);
+                    tokio::time::sleep(RECHECK_INTERVAL).await;
+                    continue;
+                }
+            };
+            let wait = match post_delay(close, last_posted, Utc::now().timestamp()) {
+                Some(wait) => wait,
+                None => {
+                    tokio::time::sleep(RECHECK_INTERVAL).await;
+                    continue;
+                }
+            };
+            tokio::time::sleep(Duration::from_secs(wait as u64)).await;
+            last_posted = Some(close);
+
+            match find_universe(Some(&schedule.universe)) {
+                Some(universe) => {
+                    let posted = post(&http, ChannelId::new(schedule.channel_id), &universe, Theme::default()).await;
+                    if let Err(why) = posted {
+                        println!("Error posting scheduled heatmap: {}", why);
+                    }
+                }
+                None => println!("Unknown heatmap universe `{}`", schedule.universe),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(symbol: &str, sector: &str, market_cap: f64) -> Tile {
+        Tile {
+            symbol: symbol.to_string(),
+            sector: sector.to_string(),
+            market_cap,
+            change: 0.0,
+        }
+    }
+
+    #[test]
+    fn it_parses_universes() {
+        let universes = parse_universes("Banks=JPM, $bac,WFC;empty=;=AAPL;chips=NVDA");
+        assert_eq!(
+            universes,
+            [
+                Universe {
+                    name: "banks".to_string(),
+                    symbols: vec!["JPM".to_string(), "BAC".to_string(), "WFC".to_string()],
+                },
+                Universe {
+                    name: "chips".to_string(),
+                    symbols: vec!["NVDA".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_squarifies_proportionally() {
+        let bounds = Rect {
+            x: 10.0,
+            y: 20.0,
+            width: 600.0,
+            height: 400.0,
+        };
+        // The example from the squarified treemap paper, scaled up.
+        let weights = [6.0, 6.0, 4.0, 3.0, 2.0, 2.0, 1.0];
+        let rects = squarify(&weights, bounds);
+
+        assert_eq!(rects.len(), weights.len());
+        for (rect, weight) in rects.iter().zip(&weights) {
+            assert!((rect.area() - weight / 24.0 * bounds.area()).abs() < 1e-6);
+            assert!(rect.x >= bounds.x - 1e-9 && rect.x + rect.width <= bounds.x + bounds.width + 1e-9);
+            assert!(rect.y >= bounds.y - 1e-9 && rect.y + rect.height <= bounds.y + bounds.height + 1e-9);
+        }
+        // The first row is the two largest, stacked down the left side.
+        assert_eq!((rects[0].x, rects[1].x), (10.0, 10.0));
+        assert!((rects[0].width - 300.0).abs() < 1e-9);
+        assert!(rects.iter().all(|r| r.width.max(r.height) / r.width.min(r.height) < 3.0));
+    }
+
+    #[test]
+    fn it_groups_tiles_by_sector() {
+        let tiles = [
+            tile("XOM", "Energy", 200.0),
+            tile("AAPL", "Technology", 2000.0),
+            tile("CVX", "Energy", 300.0),
+            tile("MSFT", "Technology", 1800.0),
+        ];
+        let bounds = Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 800.0,
+            height: 500.0,
+        };
+
+        let sectors = layout(&tiles, bounds, 16.0);
+        assert_eq!(sectors[0].name, "Technology");
+        assert_eq!(sectors[1].name, "Energy");
+        assert_eq!(sectors[1].tiles[0].0.symbol, "CVX");
+        assert!((sectors[0].bounds.area() / bounds.area() - 3800.0 / 4300.0).abs() < 1e-9);
+
+        let header = sectors[0].header.unwrap();
+        let tiles_area = sectors[0].tiles.iter().map(|(_, r)| r.area()).sum::<f64>();
+        assert!((tiles_area + header.area() - sectors[0].bounds.area()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn it_schedules_posts_after_the_close() {
+        // Monday 2021-02-22's close at 21:00 UTC.
+        let close = 1614027600;
+        let midday = close - 5 * 60 * 60;
+        assert_eq!(post_delay(close, None, midday), Some(5 * 60 * 60 + CLOSE_DELAY_SECS));
+        assert_eq!(post_delay(close, None, close + CLOSE_DELAY_SECS), Some(0));
+
+        // Posted already, or started up too late to.
+        assert_eq!(post_delay(close, Some(close), midday), None);
+        assert_eq!(post_delay(close, None, close + CLOSE_DELAY_SECS + 1), None);
+    }
+}
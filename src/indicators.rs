@@ -0,0 +1,635 @@
+//! Every indicator comes in two forms: a streaming struct fed one value or bar
+//! at a time through `next`, and a function over a whole `&[Price]` series.
+//! The series functions return one entry per price, `None` until the
+//! indicator has seen enough bars to produce a value.
+
+use crate::common::{MarketHours, Price};
+use std::collections::VecDeque;
+
+/// Simple moving average over the last `period` values.
+#[derive(Debug, Clone)]
+pub struct Sma {
+    period: usize,
+    window: VecDeque<f64>,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Sma {
+        assert!(period > 0, "period must be positive");
+        Sma {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            sum: 0.0,
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.window.push_back(value);
+        self.sum += value;
+        if self.window.len() > self.period {
+            self.sum -= self.window.pop_front().unwrap();
+        }
+
+        if self.window.len() == self.period {
+            Some(self.sum / self.period as f64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Exponential moving average, seeded with the simple average of the first
+/// `period` values.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    alpha: f64,
+    seed: Sma,
+    value: Option<f64>,
+}
+
+impl Ema {
+    pub fn new(period: usize) -> Ema {
+        Ema {
+            alpha: 2.0 / (period as f64 + 1.0),
+            seed: Sma::new(period),
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.value = match self.value {
+            Some(prev) => Some(prev + self.alpha * (value - prev)),
+            None => self.seed.next(value),
+        };
+        self.value
+    }
+}
+
+/// Linearly weighted moving average, the newest value weighted `period`.
+#[derive(Debug, Clone)]
+pub struct Wma {
+    period: usize,
+    window: VecDeque<f64>,
+}
+
+impl Wma {
+    pub fn new(period: usize) -> Wma {
+        assert!(period > 0, "period must be positive");
+        Wma {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        self.window.push_back(value);
+        if self.window.len() > self.period {
+            self.window.pop_front();
+        }
+        if self.window.len() < self.period {
+            return None;
+        }
+
+        let weighted = self
+            .window
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i + 1) as f64 * v)
+            .sum::<f64>();
+        let weights = (self.period * (self.period + 1) / 2) as f64;
+        Some(weighted / weights)
+    }
+}
+
+/// Volume weighted average of the typical price, restarting each trading day.
+#[derive(Debug, Clone, Default)]
+pub struct Vwap {
+    market_hours: MarketHours,
+    day: Option<i64>,
+    price_volume: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new() -> Vwap {
+        Vwap::default()
+    }
+
+    pub fn next(&mut self, price: &Price) -> Option<f64> {
+        let day = self.market_hours.trading_day(price.date);
+        if self.day != Some(day) {
+            self.day = Some(day);
+            self.price_volume = 0.0;
+            self.volume = 0.0;
+        }
+
+        self.price_volume += typical_price(price) * price.volume as f64;
+        self.volume += price.volume as f64;
+
+        if self.volume > 0.0 {
+            Some(self.price_volume / self.volume)
+        } else {
+            None
+        }
+    }
+}
+
+/// Relative strength index using Wilder's smoothing.
+#[derive(Debug, Clone)]
+pub struct Rsi {
+    period: usize,
+    prev: Option<f64>,
+    seen: usize,
+    avg_gain: f64,
+    avg_loss: f64,
+}
+
+impl Rsi {
+    pub fn new(period: usize) -> Rsi {
+        assert!(period > 0, "period must be positive");
+        Rsi {
+            period,
+            prev: None,
+            seen: 0,
+            avg_gain: 0.0,
+            avg_loss: 0.0,
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<f64> {
+        let prev = self.prev.replace(value)?;
+        let change = value - prev;
+        let (gain, loss) = (change.max(0.0), (-change).max(0.0));
+
+        let period = self.period as f64;
+        self.seen += 1;
+        if self.seen <= self.period {
+            // Simple average of the first `period` changes.
+            self.avg_gain += gain / period;
+            self.avg_loss += loss / period;
+            if self.seen < self.period {
+                return None;
+            }
+        } else {
+            self.avg_gain = (self.avg_gain * (period - 1.0) + gain) / period;
+            self.avg_loss = (self.avg_loss * (period - 1.0) + loss) / period;
+        }
+
+        if self.avg_loss == 0.0 {
+            return Some(if self.avg_gain == 0.0 { 50.0 } else { 100.0 });
+        }
+        Some(100.0 - 100.0 / (1.0 + self.avg_gain / self.avg_loss))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MacdPoint {
+    pub macd: f64,
+    /// `None` until the signal line has warmed up.
+    pub signal: Option<f64>,
+    pub histogram: Option<f64>,
+}
+
+/// Moving average convergence/divergence: the fast EMA less the slow EMA, with
+/// an EMA of that difference as the signal line.
+#[derive(Debug, Clone)]
+pub struct Macd {
+    fast: Ema,
+    slow: Ema,
+    signal: Ema,
+}
+
+impl Macd {
+    pub fn new(fast: usize, slow: usize, signal: usize) -> Macd {
+        Macd {
+            fast: Ema::new(fast),
+            slow: Ema::new(slow),
+            signal: Ema::new(signal),
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<MacdPoint> {
+        // Both averages have to see every value, even while warming up.
+        let (fast, slow) = (self.fast.next(value), self.slow.next(value));
+        let macd = fast? - slow?;
+        let signal = self.signal.next(macd);
+
+        Some(MacdPoint {
+            macd,
+            signal,
+            histogram: signal.map(|s| macd - s),
+        })
+    }
+}
+
+impl Default for Macd {
+    fn default() -> Self {
+        Macd::new(12, 26, 9)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bands {
+    pub upper: f64,
+    pub middle: f64,
+    pub lower: f64,
+}
+
+/// Bollinger Bands: the simple average plus and minus `width` population
+/// standard deviations.
+#[derive(Debug, Clone)]
+pub struct Bollinger {
+    width: f64,
+    sma: Sma,
+}
+
+impl Bollinger {
+    pub fn new(period: usize, width: f64) -> Bollinger {
+        Bollinger {
+            width,
+            sma: Sma::new(period),
+        }
+    }
+
+    pub fn next(&mut self, value: f64) -> Option<Bands> {
+        let middle = self.sma.next(value)?;
+        let window = &self.sma.window;
+        let variance = window.iter().map(|v| (v - middle).powi(2)).sum::<f64>() / window.len() as f64;
+        let offset = self.width * variance.sqrt();
+
+        Some(Bands {
+            upper: middle + offset,
+            middle,
+            lower: middle - offset,
+        })
+    }
+}
+
+impl Default for Bollinger {
+    fn default() -> Self {
+        Bollinger::new(20, 2.0)
+    }
+}
+
+/// Average true range using Wilder's smoothing.
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    prev_close: Option<f64>,
+    seed: Sma,
+    value: Option<f64>,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Atr {
+        Atr {
+            period,
+            prev_close: None,
+            seed: Sma::new(period),
+            value: None,
+        }
+    }
+
+    pub fn next(&mut self, price: &Price) -> Option<f64> {
+        let range = true_range(price, self.prev_close.replace(price.close));
+        let period = self.period as f64;
+
+        self.value = match self.value {
+            Some(prev) => Some((prev * (period - 1.0) + range) / period),
+            None => self.seed.next(range),
+        };
+        self.value
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StochasticPoint {
+    pub k: f64,
+    /// `None` until enough %K values have been seen to average.
+    pub d: Option<f64>,
+}
+
+/// Stochastic oscillator: where the close sits in the high/low range of the
+/// last `period` bars, with %D a simple average of %K.
+#[derive(Debug, Clone)]
+pub struct Stochastic {
+    period: usize,
+    window: VecDeque<Price>,
+    d: Sma,
+}
+
+impl Stochastic {
+    pub fn new(period: usize, d_period: usize) -> Stochastic {
+        assert!(period > 0, "period must be positive");
+        Stochastic {
+            period,
+            window: VecDeque::with_capacity(period + 1),
+            d: Sma::new(d_period),
+        }
+    }
+
+    pub fn next(&mut self, price: &Price) -> Option<StochasticPoint> {
+        self.window.push_back(*price);
+        if self.window.len() > self.period {
+            self.window.pop_front();
+        }
+        if self.window.len() < self.period {
+            return None;
+        }
+
+        let high = self.window.iter().map(|p| p.high).fold(f64::MIN, f64::max);
+        let low = self.window.iter().map(|p| p.low).fold(f64::MAX, f64::min);
+        let k = if high > low {
+            100.0 * (price.close - low) / (high - low)
+        } else {
+            50.0
+        };
+
+        Some(StochasticPoint { k, d: self.d.next(k) })
+    }
+}
+
+impl Default for Stochastic {
+    fn default() -> Self {
+        Stochastic::new(14, 3)
+    }
+}
+
+/// On-balance volume, starting from zero at the first bar.
+#[derive(Debug, Clone, Default)]
+pub struct Obv {
+    prev_close: Option<f64>,
+    value: f64,
+}
+
+impl Obv {
+    pub fn new() -> Obv {
+        Obv::default()
+    }
+
+    pub fn next(&mut self, price: &Price) -> f64 {
+        if let Some(prev) = self.prev_close {
+            let volume = price.volume as f64;
+            if price.close > prev {
+                self.value += volume;
+            } else if price.close < prev {
+                self.value -= volume;
+            }
+        }
+        self.prev_close = Some(price.close);
+        self.value
+    }
+}
+
+fn typical_price(price: &Price) -> f64 {
+    (price.high + price.low + price.close) / 3.0
+}
+
+fn true_range(price: &Price, prev_close: Option<f64>) -> f64 {
+    let range = price.high - price.low;
+    match prev_close {
+        Some(prev) => range.max((price.high - prev).abs()).max((price.low - prev).abs()),
+        None => range,
+    }
+}
+
+pub fn sma(prices: &[Price], period: usize) -> Vec<Option<f64>> {
+    let mut sma = Sma::new(period);
+    prices.iter().map(|p| sma.next(p.close)).collect()
+}
+
+pub fn ema(prices: &[Price], period: usize) -> Vec<Option<f64>> {
+    let mut ema = Ema::new(period);
+    prices.iter().map(|p| ema.next(p.close)).collect()
+}
+
+pub fn wma(prices: &[Price], period: usize) -> Vec<Option<f64>> {
+    let mut wma = Wma::new(period);
+    prices.iter().map(|p| wma.next(p.close)).collect()
+}
+
+pub fn vwap(prices: &[Price]) -> Vec<Option<f64>> {
+    let mut vwap = Vwap::new();
+    prices.iter().map(|p| vwap.next(p)).collect()
+}
+
+pub fn rsi(prices: &[Price], period: usize) -> Vec<Option<f64>> {
+    let mut rsi = Rsi::new(period);
+    prices.iter().map(|p| rsi.next(p.close)).collect()
+}
+
+pub fn macd(prices: &[Price], fast: usize, slow: usize, signal: usize) -> Vec<Option<MacdPoint>> {
+    let mut macd = Macd::new(fast, slow, signal);
+    prices.iter().map(|p| macd.next(p.close)).collect()
+}
+
+pub fn bollinger(prices: &[Price], period: usize, width: f64) -> Vec<Option<Bands>> {
+    let mut bollinger = Bollinger::new(period, width);
+    prices.iter().map(|p| bollinger.next(p.close)).collect()
+}
+
+pub fn atr(prices: &[Price], period: usize) -> Vec<Option<f64>> {
+    let mut atr = Atr::new(period);
+    prices.iter().map(|p| atr.next(p)).collect()
+}
+
+pub fn stochastic(prices: &[Price], period: usize, d_period: usize) -> Vec<Option<StochasticPoint>> {
+    let mut stochastic = Stochastic::new(period, d_period);
+    prices.iter().map(|p| stochastic.next(p)).collect()
+}
+
+pub fn obv(prices: &[Price]) -> Vec<f64> {
+    let mut obv = Obv::new();
+    prices.iter().map(|p| obv.next(p)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Closing prices from the StockCharts moving average and RSI worked examples.
+    const EMA_CLOSES: [f64; 30] = [
+        22.27, 22.19, 22.08, 22.17, 22.18, 22.13, 22.23, 22.43, 22.24, 22.29, 22.15, 22.39, 22.38, 22.61, 23.36,
+        24.05, 23.75, 23.83, 23.95, 23.63, 23.82, 23.87, 23.65, 23.19, 23.10, 23.33, 22.68, 23.10, 22.40, 22.17,
+    ];
+    const RSI_CLOSES: [f64; 33] = [
+        44.34, 44.09, 44.15, 43.61, 44.33, 44.83, 45.10, 45.42, 45.84, 46.08, 45.89, 46.03, 45.61, 46.28, 46.28,
+        46.00, 46.03, 46.41, 46.22, 45.64, 46.21, 46.25, 45.71, 46.45, 45.78, 45.35, 44.03, 44.18, 44.22, 44.57,
+        43.42, 42.66, 43.13,
+    ];
+
+    fn closes(closes: &[f64]) -> Vec<Price> {
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Price {
+                close: *close,
+                date: 1613745000 + i as i64 * 60,
+                ..Price::default()
+            })
+            .collect()
+    }
+
+    fn bars(bars: &[(f64, f64, f64, u64)]) -> Vec<Price> {
+        bars.iter()
+            .enumerate()
+            .map(|(i, (high, low, close, volume))| Price {
+                close: *close,
+                volume: *volume,
+                high: *high,
+                low: *low,
+                open: *close,
+                date: 1613745000 + i as i64 * 60,
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: &[Option<f64>], expected: &[f64], tolerance: f64) {
+        let actual = actual.iter().flatten().copied().collect::<Vec<f64>>();
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() <= tolerance, "{} != {}", a, e);
+        }
+    }
+
+    #[test]
+    fn it_computes_simple_and_weighted_averages() {
+        let prices = closes(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+
+        assert_eq!(sma(&prices, 3), [None, None, Some(2.0), Some(3.0), Some(4.0)]);
+        assert_close(&wma(&prices, 3), &[14.0 / 6.0, 20.0 / 6.0, 26.0 / 6.0], 1e-9);
+    }
+
+    #[test]
+    fn it_matches_the_reference_ema() {
+        let ema = ema(&closes(&EMA_CLOSES), 10);
+        assert!(ema[..9].iter().all(Option::is_none));
+        assert_close(
+            &ema,
+            &[
+                22.22, 22.21, 22.24, 22.27, 22.33, 22.52, 22.80, 22.97, 23.13, 23.28, 23.34, 23.43, 23.51, 23.53,
+                23.47, 23.40, 23.39, 23.26, 23.23, 23.08, 22.92,
+            ],
+            0.005,
+        );
+    }
+
+    #[test]
+    fn it_matches_the_reference_rsi() {
+        let values = rsi(&closes(&RSI_CLOSES), 14);
+        assert!(values[..14].iter().all(Option::is_none));
+        assert_close(
+            &values,
+            &[
+                70.46, 66.25, 66.48, 69.35, 66.29, 57.92, 62.88, 63.21, 56.01, 62.34, 54.67, 50.39, 40.02, 41.49,
+                41.90, 45.50, 37.32, 33.09, 37.79,
+            ],
+            0.005,
+        );
+
+        assert_eq!(Rsi::new(2).next(1.0), None);
+        assert_eq!(rsi(&closes(&[1.0, 2.0, 3.0]), 2)[2], Some(100.0));
+    }
+
+    #[test]
+    fn it_computes_macd_from_two_emas() {
+        let prices = closes(&RSI_CLOSES);
+        let points = macd(&prices, 3, 6, 4);
+        assert!(points[..5].iter().all(Option::is_none));
+
+        let fast = ema(&prices, 3);
+        let slow = ema(&prices, 6);
+        let first = points[5].unwrap();
+        assert!((first.macd - (fast[5].unwrap() - slow[5].unwrap())).abs() < 1e-9);
+        assert_eq!(first.signal, None);
+
+        // The signal line seeds from the first four MACD values.
+        let signal = points[8].unwrap();
+        let seed = points[5..9].iter().map(|p| p.unwrap().macd).sum::<f64>() / 4.0;
+        assert!((signal.signal.unwrap() - seed).abs() < 1e-9);
+        assert!((signal.histogram.unwrap() - (signal.macd - seed)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_computes_bollinger_bands() {
+        let bands = bollinger(&closes(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]), 8, 2.0);
+        assert!(bands[..7].iter().all(Option::is_none));
+        // Mean 5, population standard deviation 2.
+        assert_eq!(
+            bands[7],
+            Some(Bands {
+                upper: 9.0,
+                middle: 5.0,
+                lower: 1.0
+            })
+        );
+    }
+
+    #[test]
+    fn it_computes_average_true_range() {
+        let prices = bars(&[
+            (10.0, 8.0, 9.0, 0),
+            (12.0, 9.5, 11.0, 0),
+            (11.5, 10.0, 10.5, 0),
+            (10.0, 7.0, 8.0, 0),
+        ]);
+        // True ranges: 2, 3 (12 - 9), 1.5, 3.5 (10.5 - 7).
+        assert_close(&atr(&prices, 2), &[2.5, 2.0, 2.75], 1e-9);
+    }
+
+    #[test]
+    fn it_computes_the_stochastic_oscillator() {
+        let prices = bars(&[
+            (10.0, 8.0, 9.0, 0),
+            (12.0, 9.0, 11.0, 0),
+            (11.0, 10.0, 10.0, 0),
+            (13.0, 10.0, 13.0, 0),
+        ]);
+        let points = stochastic(&prices, 2, 2);
+
+        assert_eq!(points[0], None);
+        assert_eq!(points[1], Some(StochasticPoint { k: 75.0, d: None }));
+        let point = points[2].unwrap();
+        assert!((point.k - 100.0 / 3.0).abs() < 1e-9);
+        assert!((point.d.unwrap() - (75.0 + 100.0 / 3.0) / 2.0).abs() < 1e-9);
+        assert_eq!(points[3].unwrap().k, 100.0);
+    }
+
+    #[test]
+    fn it_computes_volume_indicators() {
+        let prices = bars(&[
+            (10.0, 8.0, 9.0, 100),
+            (12.0, 9.0, 12.0, 300),
+            (12.0, 9.0, 12.0, 50),
+            (11.0, 8.0, 8.0, 200),
+        ]);
+        assert_eq!(obv(&prices), [0.0, 300.0, 300.0, 100.0]);
+
+        let vwap = vwap(&prices);
+        assert_eq!(vwap[0], Some(9.0));
+        assert_eq!(vwap[1], Some((900.0 + 3300.0) / 400.0));
+
+        // A new trading day starts over.
+        let mut next_day = prices[3];
+        next_day.date += 24 * 60 * 60;
+        let mut stream = Vwap::new();
+        prices.iter().for_each(|p| {
+            stream.next(p);
+        });
+        assert_eq!(stream.next(&next_day), Some(9.0));
+    }
+
+    #[test]
+    fn it_streams_the_same_values_as_the_series() {
+        let prices = closes(&RSI_CLOSES);
+        let mut ema = Ema::new(10);
+        let mut bollinger = Bollinger::default();
+
+        let streamed = prices.iter().map(|p| ema.next(p.close)).collect::<Vec<Option<f64>>>();
+        assert_eq!(streamed, super::ema(&prices, 10));
+
+        let streamed = prices.iter().map(|p| bollinger.next(p.close)).collect::<Vec<Option<Bands>>>();
+        assert_eq!(streamed, super::bollinger(&prices, 20, 2.0));
+    }
+}
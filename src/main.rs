@@ -3,87 +3,1020 @@ extern crate stout_api as api;
 
 mod stock;
 mod common;
+mod compare;
+mod events;
+mod heatmap;
 mod plot;
+mod indicators;
+mod options;
+mod ratelimit;
+mod scanner;
+mod settings;
+mod slash;
+mod storage;
+mod strategy;
+mod symbols;
+mod theme;
 
 use tokio;
 
 use lazy_static::lazy_static;
 use regex::Regex;
-use std::path::Path;
 use serenity::client::{Client, Context, EventHandler};
 use serenity::{
-    utils,
     async_trait,
-    model::channel::Message,
+    model::channel::{Message, ReactionType},
+    model::id::{ChannelId, UserId},
+    model::gateway::{GatewayIntents, Ready},
+    model::application::Interaction,
     //prelude::*,
     //utils::MessageBuilder,
-    http::AttachmentType,
+    builder::{CreateAttachment, CreateEmbed, CreateEmbedFooter, CreateMessage, EditMessage},
 };
 
+#[allow(deprecated)]
 use serenity::framework::standard::{
-    macros::{command, group},
-    CommandResult, StandardFramework,
+    help_commands,
+    macros::{command, group, help, hook},
+    Args, CommandGroup, CommandResult, Configuration, HelpOptions, StandardFramework,
 };
 
+use futures::future::join_all;
+use std::collections::HashSet;
 use std::env;
+use std::time::Duration;
+
+use common::TimeFrame;
+use plot::ChartType;
+use theme::Theme;
 
 lazy_static! {
     static ref CLIENT: api::Client = api::Client::new();
-    static ref SYMBOL_RE: Regex = Regex::new(r"\$([A-Z]{1,5})(\+)?(\W|$)").unwrap();
+    static ref SYMBOL_RE: Regex = Regex::new(r"\$(\^[A-Z0-9]{1,6}|[A-Z]{6}=X|[A-Z][A-Z0-9]{0,4}(?:=[FX]|-[A-Z]{3}|\.[A-Z]{1,2})?)(\+)?((?::[0-9A-Za-z%]+)*)(\W|$)").unwrap();
 }
 
+const PREFIX: &str = "~";
+/// Messages mentioning at least this many symbols get one sparkline grid
+/// instead of an embed per symbol.
+const SPARKLINE_MIN_SYMBOLS: usize = 2;
+/// Reactions that step a chart to the previous and next time frame.
+const CONTROLS: [&str; 2] = ["◀\u{FE0F}", "▶\u{FE0F}"];
+const CONTROLS_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+/// Company descriptions run long; `~info` keeps the first few sentences.
+const INFO_DESCRIPTION_CHARS: usize = 500;
+
 #[group]
+#[allow(deprecated)]
+#[commands(quote, chart, info, implied_move, iv, payoff, compare, heatmap, replay, theme, server_theme)]
 struct General;
 
+#[command("move")]
+#[allow(deprecated)]
+#[description = "Expected move from the at-the-money straddle, e.g. `~move $AAPL`"]
+async fn implied_move(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let symbol = match symbol_arg(&mut args) {
+        Some(symbol) => symbols::resolve(&symbol),
+        None => {
+            msg.reply(context, "Usage: `~move $SYMBOL`").await?;
+            return Ok(());
+        }
+    };
+
+    let moves = match options::fetch_expected_moves(&symbol).await {
+        Ok(moves) => moves,
+        Err(why) => {
+            println!("Error fetching implied move for {}: {:?}", symbol, why);
+            msg.reply(context, format!("Couldn't get options data for ${}", symbol)).await?;
+            return Ok(());
+        }
+    };
+
+    let format_move = |m: &options::ImpliedMove| {
+        format!("±${:.2} (±{:.2}%)\n{} straddle", m.straddle, m.pct * 100.0, m.strike)
+    };
+
+    let mut embed = CreateEmbed::new().title(format!("{} - implied move", symbol));
+    if let Some(nearest) = &moves.nearest {
+        embed = embed.field(
+            format!("Nearest ({})", options::format_expiration(nearest.expiration_date)),
+            format_move(nearest),
+            true,
+        );
+    }
+    if let (Some(earnings_date), Some(earnings)) = (moves.earnings_date, &moves.earnings) {
+        embed = embed.field(
+            format!(
+                "Earnings {} ({})",
+                options::format_expiration(earnings_date),
+                options::format_expiration(earnings.expiration_date)
+            ),
+            format_move(earnings),
+            true,
+        );
+    }
+    if moves.nearest.is_none() && moves.earnings.is_none() {
+        embed = embed.description("No listed options");
+    }
+
+    msg.channel_id
+        .send_message(&context.http, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Implied volatility smile and ATM term structure, e.g. `~iv $AAPL [2021-03-19]`"]
+async fn iv(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let symbol = match symbol_arg(&mut args) {
+        Some(symbol) => symbols::resolve(&symbol),
+        None => {
+            msg.reply(context, "Usage: `~iv $SYMBOL [YYYY-MM-DD]`").await?;
+            return Ok(());
+        }
+    };
+    let expiry = args.single::<String>().ok();
+
+    let chain = match options::fetch_option_chain(&symbol).await {
+        Ok(chain) => chain,
+        Err(why) => {
+            println!("Error fetching options for {}: {:?}", symbol, why);
+            msg.reply(context, format!("Couldn't get options data for ${}", symbol)).await?;
+            return Ok(());
+        }
+    };
+
+    let smile_data = match &expiry {
+        Some(expiry) => chain
+            .iter()
+            .flat_map(|header| header.options.iter().map(move |data| (header, data)))
+            .find(|(_, data)| &options::format_expiration(data.expiration_date) == expiry),
+        None => chain
+            .iter()
+            .flat_map(|header| header.options.iter().map(move |data| (header, data)))
+            .min_by_key(|(_, data)| data.expiration_date),
+    };
+
+    let (header, data) = match smile_data {
+        Some(smile_data) => smile_data,
+        None => {
+            let dates = chain
+                .first()
+                .map(|header| header.expiration_dates.iter().map(|d| options::format_expiration(*d)).collect())
+                .unwrap_or_else(Vec::new);
+            let reply = if dates.is_empty() {
+                format!("No listed options for ${}", symbol)
+            } else {
+                format!("No ${} options expiring then. Expirations: {}", symbol, dates.join(", "))
+            };
+            msg.reply(context, reply).await?;
+            return Ok(());
+        }
+    };
+
+    let theme = theme_for(msg).await;
+    let smile = plot::build_iv_smile_chart(
+        &symbol,
+        data.expiration_date,
+        header.quote.regular_market_price,
+        &options::iv_smile(&data.calls),
+        &options::iv_smile(&data.puts),
+        theme,
+    )
+    .map_err(|e| e.to_string());
+    let term = plot::build_iv_term_structure_chart(&symbol, &options::term_structure(&chain), theme)
+        .map_err(|e| e.to_string());
+
+    let files = vec![(smile, "smile.png"), (term, "term.png")]
+        .into_iter()
+        .filter_map(|(png, filename)| match png {
+            Ok(png) => Some(CreateAttachment::bytes(png, filename)),
+            Err(why) => {
+                println!("Error rendering {} for {}: {}", filename, symbol, why);
+                None
+            }
+        })
+        .collect::<Vec<CreateAttachment>>();
+
+    if files.is_empty() {
+        msg.reply(context, format!("No implied volatility data for ${}", symbol)).await?;
+        return Ok(());
+    }
+
+    msg.channel_id.send_files(&context.http, files, CreateMessage::new()).await?;
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Profit/loss at expiry for an options strategy, e.g. `~payoff $AAPL +1 150c -1 160c 2024-06-21`"]
+async fn payoff(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let symbol = match symbol_arg(&mut args) {
+        Some(symbol) => symbols::resolve(&symbol),
+        None => {
+            msg.reply(context, strategy::USAGE).await?;
+            return Ok(());
+        }
+    };
+    let tokens = args.rest().split_whitespace().collect::<Vec<&str>>();
+
+    let request = match strategy::parse_legs(&tokens) {
+        Ok(request) => request,
+        Err(why) => {
+            msg.reply(context, why).await?;
+            return Ok(());
+        }
+    };
+
+    let (strategy, underlying_price) = match strategy::fetch_strategy(&symbol, &request).await {
+        Ok(priced) => priced,
+        Err(why) => {
+            msg.reply(context, why).await?;
+            return Ok(());
+        }
+    };
+
+    let chart = plot::build_payoff_chart(&symbol, &strategy, underlying_price, theme_for(msg).await).map_err(|e| e.to_string());
+
+    let format_extreme = |v: Option<f64>| v.map_or_else(|| "Unlimited".to_string(), strategy::format_pnl);
+    let legs = strategy
+        .legs
+        .iter()
+        .map(|l| format!("{:+} {}{} @ {:.2}", l.leg.quantity, l.leg.strike, l.leg.kind, l.price))
+        .collect::<Vec<String>>();
+    let breakevens = strategy
+        .breakevens()
+        .iter()
+        .map(|b| format!("${:.2}", b))
+        .collect::<Vec<String>>();
+    let net_debit = strategy.net_debit();
+
+    let mut embed = CreateEmbed::new()
+        .title(format!(
+            "{} - {} payoff",
+            symbol,
+            options::format_expiration(strategy.expiration_date)
+        ))
+        .description(legs.join("\n"))
+        .fields(vec![
+            (
+                if net_debit >= 0.0 { "Net debit" } else { "Net credit" },
+                format!("${:.2}", net_debit.abs()),
+                true,
+            ),
+            ("Max profit", format_extreme(strategy.max_profit()), true),
+            ("Max loss", format_extreme(strategy.max_loss()), true),
+        ]);
+    if !breakevens.is_empty() {
+        embed = embed.field("Breakevens", breakevens.join(", "), false);
+    }
+    let mut message = CreateMessage::new();
+    match chart {
+        Ok(png) => {
+            embed = embed.attachment("payoff.png");
+            message = message.add_file(CreateAttachment::bytes(png, "payoff.png"));
+        }
+        Err(why) => println!("Error rendering payoff chart for {}: {}", symbol, why),
+    }
+
+    let msg = msg.channel_id.send_message(&context.http, message.embed(embed)).await;
+    if let Err(why) = msg {
+        println!("Error sending message: {:?}", why);
+    }
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Percent change of several symbols on one chart, e.g. `~compare $AAPL $MSFT $QQQ 1Y`"]
+async fn compare(context: &Context, msg: &Message, args: Args) -> CommandResult {
+    let tokens = args.rest().split_whitespace().collect::<Vec<&str>>();
+    let request = match compare::parse_request(&tokens) {
+        Ok(request) => request,
+        Err(why) => {
+            msg.reply(context, why).await?;
+            return Ok(());
+        }
+    };
+
+    let comparison = match compare::fetch_comparison(&request).await {
+        Ok(comparison) => comparison,
+        Err(why) => {
+            msg.reply(context, why).await?;
+            return Ok(());
+        }
+    };
+
+    let chart = plot::build_comparison_chart(&comparison, theme_for(msg).await).map_err(|e| e.to_string());
+    let returns = comparison
+        .final_returns()
+        .iter()
+        .map(|(symbol, change)| format!("{} {:+.2}%", symbol, change * 100.0))
+        .collect::<Vec<String>>();
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("{} - {}", request.symbols.join(" vs "), request.time_frame.as_str()))
+        .description(returns.join(" · "));
+    let mut message = CreateMessage::new();
+    match chart {
+        Ok(png) => {
+            embed = embed.attachment("compare.png");
+            message = message.add_file(CreateAttachment::bytes(png, "compare.png"));
+        }
+        Err(why) => println!("Error rendering comparison chart: {}", why),
+    }
+
+    let msg = msg.channel_id.send_message(&context.http, message.embed(embed)).await;
+    if let Err(why) = msg {
+        println!("Error sending message: {:?}", why);
+    }
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Animated replay of today's price action, e.g. `~replay $TSLA` or `~replay $TSLA candle`"]
+async fn replay(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let usage = "Usage: `~replay $SYMBOL [line|candle|ohlc]`";
+    let symbol = match args.single::<String>() {
+        Ok(symbol) => symbol.trim_start_matches('$').to_uppercase(),
+        Err(_) => {
+            msg.reply(context, usage).await?;
+            return Ok(());
+        }
+    };
+    let chart_type = match args.single::<String>().map(|arg| arg.parse::<ChartType>()) {
+        Ok(Ok(chart_type)) => chart_type,
+        Ok(Err(why)) => {
+            msg.reply(context, why).await?;
+            return Ok(());
+        }
+        Err(_) => ChartType::Line,
+    };
+
+    let stock = match stock::Stock::new(&symbol, TimeFrame::Day1).await {
+        Ok(stock) => stock,
+        Err(why) => {
+            msg.reply(context, why).await?;
+            return Ok(());
+        }
+    };
+    let options = plot::ChartOptions {
+        chart_type,
+        theme: theme_for(msg).await,
+        ..plot::ChartOptions::for_time_frame(TimeFrame::Day1)
+    };
+    // Encoding every frame takes a while, so keep it off the async workers.
+    let replay = tokio::task::spawn_blocking(move || {
+        let gif = plot::build_replay(&stock, options).map_err(|why| why.to_string());
+        (stock, gif)
+    })
+    .await?;
+
+    let (stock, gif) = match replay {
+        (stock, Ok(gif)) => (stock, gif),
+        (_, Err(why)) => {
+            println!("Error rendering replay for {}: {}", symbol, why);
+            msg.reply(context, format!("Couldn't replay ${}", symbol)).await?;
+            return Ok(());
+        }
+    };
+
+    let embed = CreateEmbed::new()
+        .title(format!("{} - replay", stock.symbol))
+        .description(format!("${:.2} ({:+.2}%)", stock.current_price, stock.pct_change * 100.0))
+        .attachment("replay.gif");
+    let msg = msg
+        .channel_id
+        .send_message(
+            &context.http,
+            CreateMessage::new().embed(embed).add_file(CreateAttachment::bytes(gif, "replay.gif")),
+        )
+        .await;
+    if let Err(why) = msg {
+        println!("Error sending message: {:?}", why);
+    }
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Price, change and day range without a chart, e.g. `~quote $AAPL`"]
+async fn quote(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let symbol = match symbol_arg(&mut args) {
+        Some(symbol) => symbol,
+        None => {
+            msg.reply(context, "Usage: `~quote $SYMBOL`").await?;
+            return Ok(());
+        }
+    };
+
+    let stock = match stock::Stock::new(&symbol, TimeFrame::Day1).await {
+        Ok(stock) => stock,
+        Err(why) => {
+            msg.reply(context, why).await?;
+            return Ok(());
+        }
+    };
+    let sent = msg
+        .channel_id
+        .send_message(&context.http, CreateMessage::new().embed(quote_embed(&stock)))
+        .await;
+    if let Err(why) = sent {
+        println!("Error sending message: {:?}", why);
+    }
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Quote and chart, the same as mentioning `$AAPL` in a message, e.g. `~chart $AAPL 3M candle sma50`"]
+async fn chart(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let usage = "Usage: `~chart $SYMBOL [1D|1W|1M|3M|6M|1Y|5Y] [line|candle|ohlc] [linear|log|pct] [png|svg] [indicators...]`";
+    let symbol = match symbol_arg(&mut args) {
+        Some(symbol) => symbol,
+        None => {
+            msg.reply(context, usage).await?;
+            return Ok(());
+        }
+    };
+    let (time_frame, chart_options, unknown) = read_chart_options(args.rest().split_whitespace());
+    if let Some(word) = unknown {
+        msg.reply(context, format!("Didn't understand `{}`. {}", word, usage)).await?;
+        return Ok(());
+    }
+
+    match stock::Stock::new(&symbol, time_frame).await {
+        Ok(stock) => send_chart(context, msg, &stock, false, chart_options).await,
+        Err(why) => {
+            msg.reply(context, why).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Company profile: sector, industry, size and what the business does, e.g. `~info $AAPL`"]
+async fn info(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let symbol = match symbol_arg(&mut args) {
+        Some(symbol) => symbol,
+        None => {
+            msg.reply(context, "Usage: `~info $SYMBOL`").await?;
+            return Ok(());
+        }
+    };
+
+    let requested = symbol;
+    let symbol = symbols::resolve(&requested);
+    let company = match CLIENT.get_company_data(&symbol).await {
+        Ok(company) => company,
+        Err(why) => {
+            println!("Error getting company data for {}: {:?}", symbol, why);
+            msg.reply(context, stock::fetch_error(&requested, &stock::io_error(why))).await?;
+            return Ok(());
+        }
+    };
+    let sent = msg
+        .channel_id
+        .send_message(&context.http, CreateMessage::new().embed(company_embed(&symbol, company)))
+        .await;
+    if let Err(why) = sent {
+        println!("Error sending message: {:?}", why);
+    }
+
+    Ok(())
+}
+
+#[help]
+#[allow(deprecated)]
+#[individual_command_tip = "Mention `$SYMBOL` anywhere in a message for a quote and chart, e.g. `$AAPL 3M candle`.\nUse `~help <command>` for more on one command."]
+#[lacking_permissions = "Hide"]
+async fn help(
+    context: &Context,
+    msg: &Message,
+    args: Args,
+    help_options: &'static HelpOptions,
+    groups: &[&'static CommandGroup],
+    owners: HashSet<UserId>,
+) -> CommandResult {
+    help_commands::with_embeds(context, msg, args, help_options, groups, owners).await?;
+    Ok(())
+}
+
+/// Parses a theme name, or `None` for `reset`.
+fn parse_theme_arg(arg: &str) -> Result<Option<Theme>, &'static str> {
+    if arg.eq_ignore_ascii_case("reset") {
+        Ok(None)
+    } else {
+        arg.parse().map(Some)
+    }
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Your chart theme, e.g. `~theme dark`. One of light, dark, colorblind, colorblind-dark, or reset"]
+async fn theme(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let arg = match args.single::<String>() {
+        Ok(arg) => arg,
+        Err(_) => {
+            let reply = format!("Your charts use the {} theme", theme_for(msg).await);
+            msg.reply(context, reply).await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match parse_theme_arg(&arg) {
+        Ok(theme) => match settings::set_user_theme(msg.author.id.get(), theme).await {
+            Ok(()) => format!("Your charts now use the {} theme", theme_for(msg).await),
+            Err(why) => {
+                println!("Error saving settings: {:?}", why);
+                "Couldn't save your theme".to_string()
+            }
+        },
+        Err(why) => why.to_string(),
+    };
+    msg.reply(context, reply).await?;
+
+    Ok(())
+}
+
+#[command]
+#[allow(deprecated)]
+#[description = "Sector heatmap sized by market cap and colored by daily change, e.g. `~heatmap` or `~heatmap megacap`"]
+async fn heatmap(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = args.single::<String>().ok();
+    let universe = match heatmap::find_universe(name.as_deref()) {
+        Some(universe) => universe,
+        None => {
+            let names = heatmap::universes()
+                .iter()
+                .map(|u| format!("`{}`", u.name))
+                .collect::<Vec<String>>();
+            msg.reply(context, format!("Valid universes are: {}", names.join(", "))).await?;
+            return Ok(());
+        }
+    };
+
+    if let Err(why) = heatmap::post(&context.http, msg.channel_id, &universe, theme_for(msg).await).await {
+        msg.reply(context, why).await?;
+    }
+
+    Ok(())
+}
+
+#[command("servertheme")]
+#[allow(deprecated)]
+#[only_in(guilds)]
+#[required_permissions(MANAGE_GUILD)]
+#[description = "Default chart theme for this server, e.g. `~servertheme dark`, or reset"]
+async fn server_theme(context: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id.get(),
+        None => return Ok(()),
+    };
+
+    let reply = match args.single::<String>().map(|arg| parse_theme_arg(&arg)) {
+        Ok(Ok(theme)) => match settings::set_guild_theme(guild_id, theme).await {
+            Ok(()) => format!(
+                "Charts in this server now default to the {} theme",
+                theme.unwrap_or_default()
+            ),
+            Err(why) => {
+                println!("Error saving settings: {:?}", why);
+                "Couldn't save the server theme".to_string()
+            }
+        },
+        Ok(Err(why)) => why.to_string(),
+        Err(_) => "Usage: `~servertheme light|dark|colorblind|colorblind-dark|reset`".to_string(),
+    };
+    msg.reply(context, reply).await?;
+
+    Ok(())
+}
+
+/// Reads a `$SYMBOL` argument; the `$` is optional.
+#[allow(deprecated)]
+fn symbol_arg(args: &mut Args) -> Option<String> {
+    args.single::<String>()
+        .ok()
+        .map(|symbol| symbol.trim_start_matches('$').to_uppercase())
+        .filter(|symbol| !symbol.is_empty())
+}
+
+/// Shortens `text` to at most `max_chars`, breaking between words.
+fn truncate_words(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    for word in text.split_whitespace() {
+        if truncated.chars().count() + word.chars().count() + 1 >= max_chars {
+            break;
+        }
+        if !truncated.is_empty() {
+            truncated.push(' ');
+        }
+        truncated.push_str(word);
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// Checks `msg`, fetching `symbols` symbols, against the rate limits,
+/// politely telling its author the first time they go over one.
+async fn within_limits(context: &Context, msg: &Message, symbols: usize) -> bool {
+    let (user, channel, guild) = (msg.author.id.get(), msg.channel_id.get(), msg.guild_id.map(|id| id.get()));
+    match ratelimit::check(user, channel, guild, symbols) {
+        ratelimit::Verdict::Allowed => true,
+        ratelimit::Verdict::Limited { retry_after, notify } => {
+            if notify {
+                let notice = format!("That's a lot of requests, try again in {}s.", retry_after.as_secs().max(1));
+                if let Err(why) = msg.reply(context, notice).await {
+                    println!("Error sending message: {:?}", why);
+                }
+            }
+            false
+        }
+    }
+}
+
+#[hook]
+#[allow(deprecated)]
+async fn before(context: &Context, msg: &Message, _command_name: &str) -> bool {
+    within_limits(context, msg, 1).await
+}
+
+/// The theme for charts drawn in response to `msg`.
+async fn theme_for(msg: &Message) -> Theme {
+    settings::theme_for(msg.guild_id.map(|id| id.get()), msg.author.id.get()).await
+}
+
+/// A symbol's price, change and range, without a chart.
+fn quote_embed(stock: &stock::Stock) -> CreateEmbed {
+    let price = |price| {
+        let price = symbols::format_price(price, stock.instrument, stock.currency.as_deref());
+        format!("{: <8}", price)
+    };
+    let blank = || ('\u{200B}'.to_string(), '\u{200B}'.to_string(), true);
+    let period = match stock.time_frame {
+        TimeFrame::Day1 => "24hrs",
+        time_frame => time_frame.as_str(),
+    };
+
+    let size = match (&stock.contract, stock.market_cap) {
+        (Some(contract), _) => contract
+            .open_interest
+            .map_or_else(blank, |i| ("Open int.".to_string(), common::format_large_number(i), true)),
+        (None, Some(cap)) if stock.instrument.has_market_cap() => {
+            ("Cap".to_string(), common::format_large_number(cap), true)
+        }
+        _ => blank(),
+    };
+
+    let mut e = CreateEmbed::new()
+        .title(format!("{} - {}", stock.symbol, period))
+        .fields(vec![
+            ("Price".to_string(), price(stock.current_price), true),
+            size,
+            ("Change".to_string(), format!("{:.2}%", stock.pct_change * 100.0), true),
+        ])
+        .fields(vec![
+            ("Low".to_string(), price(stock.low), true),
+            blank(),
+            ("High".to_string(), price(stock.high), true),
+        ]);
+    if let Some(contract) = &stock.contract {
+        e = e.fields(vec![
+            ("Contract".to_string(), contract.name.clone(), true),
+            blank(),
+            ("Expires".to_string(), contract.expires.clone().unwrap_or_else(|| "-".to_string()), true),
+        ]);
+    }
+
+    let mut links = vec![];
+    if stock.instrument.on_stocktwits() {
+        links.push(format!("[twits](https://stocktwits.com/symbol/{})", stock.symbol));
+    }
+    links.push(format!("[yhoo]({})", symbols::yahoo_url(&stock.symbol)));
+    if let Some(website) = stock.company.as_ref().and_then(|c| c.website.as_ref()) {
+        links.push(format!("[web]({})", website));
+    }
+    e.description(links.join(" | "))
+}
+
+/// A company's profile: what it does, its sector and size.
+fn company_embed(symbol: &str, company: api::model::CompanyData) -> CreateEmbed {
+    let name = company.price.long_name.clone().unwrap_or_else(|| company.price.short_name.clone());
+    let profile = company.profile;
+
+    let mut fields = vec![];
+    if let Some(sector) = profile.as_ref().and_then(|p| p.sector.clone()) {
+        fields.push(("Sector".to_string(), sector, true));
+    }
+    if let Some(industry) = profile.as_ref().and_then(|p| p.industry.clone()) {
+        fields.push(("Industry".to_string(), industry, true));
+    }
+    if let Some(employees) = profile.as_ref().and_then(|p| p.employees.map(|e| e.to_string())) {
+        fields.push(("Employees".to_string(), employees, true));
+    }
+    if let Some(market_cap) = company.price.market_cap.and_then(|c| c.amount) {
+        fields.push(("Cap".to_string(), common::format_large_number(market_cap), true));
+    }
+    let next_earnings = company
+        .calendar_events
+        .and_then(|c| c.earnings)
+        .and_then(|e| e.earnings_date.into_iter().next())
+        .and_then(|d| d.fmt);
+    if let Some(date) = next_earnings {
+        fields.push(("Next earnings".to_string(), date, true));
+    }
+
+    let mut e = CreateEmbed::new().title(format!("{} ({})", name, symbol));
+    if let Some(website) = profile.as_ref().and_then(|p| p.website.clone()) {
+        e = e.url(website);
+    }
+    if let Some(description) = profile.as_ref().and_then(|p| p.description.clone()) {
+        e = e.description(truncate_words(&description, INFO_DESCRIPTION_CHARS));
+    }
+    e.fields(fields)
+}
+
+/// Adds what `$SYMBOL+` asks for beyond the quote: the company's sector,
+/// industry, size and a summary of the business.
+fn extended_embed(e: CreateEmbed, stock: &stock::Stock) -> CreateEmbed {
+    let company = match &stock.company {
+        Some(company) => company,
+        None => return e,
+    };
+
+    let fields = vec![
+        ("Sector", company.sector.clone()),
+        ("Industry", company.industry.clone()),
+        ("Employees", company.employees.map(|e| e.to_string())),
+    ];
+    let e = e.fields(fields.into_iter().filter_map(|(name, value)| value.map(|v| (name, v, true))));
+    match &company.description {
+        Some(description) => e.footer(CreateEmbedFooter::new(truncate_words(description, INFO_DESCRIPTION_CHARS))),
+        None => e,
+    }
+}
+
+/// A symbol's quote embed with its chart to attach, and the company's
+/// profile too when `extended`.
+fn stock_embed(
+    stock: &stock::Stock,
+    extended: bool,
+    chart_options: plot::ChartOptions,
+) -> (CreateEmbed, Option<CreateAttachment>) {
+    let chart_format = chart_options.format;
+    let chart_filename = chart_format.filename("chart");
+    let chart = match plot::build_chart(stock, chart_options) {
+        Ok(chart) => Some(chart),
+        Err(why) => {
+            println!("Error rendering chart for {}: {:?}", stock.symbol, why);
+            None
+        }
+    };
+
+    let mut embed = quote_embed(stock);
+    if extended {
+        embed = extended_embed(embed, stock);
+    }
+    // Vector charts come as a download instead, since embeds only show
+    // bitmaps.
+    if chart.is_some() && chart_format == plot::ChartFormat::Png {
+        embed = embed.attachment(&chart_filename);
+    }
+    (embed, chart.map(|chart| CreateAttachment::bytes(chart, chart_filename)))
+}
+
+/// Posts a symbol's chart in the asker's theme, with time frame controls.
+async fn send_chart(
+    context: &Context,
+    msg: &Message,
+    stock: &stock::Stock,
+    extended: bool,
+    chart_options: plot::ChartOptions,
+) {
+    let chart_options = plot::ChartOptions {
+        theme: theme_for(msg).await,
+        ..chart_options
+    };
+
+    let (embed, chart) = stock_embed(stock, extended, chart_options.clone());
+    let message = CreateMessage::new().embed(embed).files(chart);
+    match msg.channel_id.send_message(&context.http, message).await {
+        Ok(sent) => {
+            tokio::spawn(time_frame_controls(
+                context.clone(),
+                sent,
+                msg.author.id,
+                stock.symbol.clone(),
+                stock.time_frame,
+                extended,
+                chart_options,
+            ));
+        }
+        Err(why) => println!("Error sending message: {:?}", why),
+    }
+}
+
+/// The time frame step a control reaction asks for. Clients may or may not
+/// send the emoji variation selector, so it's ignored.
+fn control_step(emoji: &ReactionType) -> Option<fn(TimeFrame) -> TimeFrame> {
+    match emoji {
+        ReactionType::Unicode(name) => match name.trim_end_matches('\u{FE0F}') {
+            "◀" => Some(TimeFrame::down),
+            "▶" => Some(TimeFrame::up),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Lets whoever asked for a chart step it through time frames with ◀ and ▶,
+/// until they stop for `CONTROLS_TIMEOUT`. Each step redraws the message in
+/// place.
+async fn time_frame_controls(
+    context: Context,
+    mut message: Message,
+    user_id: UserId,
+    symbol: String,
+    mut time_frame: TimeFrame,
+    extended: bool,
+    chart_options: plot::ChartOptions,
+) {
+    for control in CONTROLS.iter() {
+        if let Err(why) = message.react(&context.http, ReactionType::Unicode(control.to_string())).await {
+            println!("Error adding chart controls: {:?}", why);
+            return;
+        }
+    }
+
+    loop {
+        let reaction = message
+            .await_reaction(&context.shard)
+            .author_id(user_id)
+            .filter(|r| control_step(&r.emoji).is_some())
+            .timeout(CONTROLS_TIMEOUT)
+            .await;
+        let (reaction, step) = match reaction.and_then(|r| control_step(&r.emoji).map(|step| (r, step))) {
+            Some(pressed) => pressed,
+            None => break,
+        };
+        // Take the press back off, so the same control can be pressed again.
+        if let Err(why) = reaction.delete(&context.http).await {
+            println!("Error removing reaction: {:?}", why);
+        }
+
+        // Stepping fetches and renders like any other request, so it counts
+        // against the same limits; over them, the press is just ignored.
+        let verdict = ratelimit::check(user_id.get(), message.channel_id.get(), message.guild_id.map(|id| id.get()), 1);
+        if verdict != ratelimit::Verdict::Allowed {
+            continue;
+        }
+
+        let stock = match stock::Stock::new(&symbol, step(time_frame)).await {
+            Ok(stock) => stock,
+            Err(why) => {
+                if let Err(why) = message.reply(&context.http, why).await {
+                    println!("Error sending message: {:?}", why);
+                }
+                continue;
+            }
+        };
+        time_frame = stock.time_frame;
+
+        let (embed, chart) = stock_embed(&stock, extended, chart_options.clone().with_time_frame(time_frame));
+        let edit = match chart {
+            Some(chart) => EditMessage::new().embed(embed).new_attachment(chart),
+            None => EditMessage::new().embed(embed).remove_all_attachments(),
+        };
+        if let Err(why) = message.edit(&context.http, edit).await {
+            println!("Error editing message: {:?}", why);
+            break;
+        }
+    }
+
+    for control in CONTROLS.iter() {
+        let removed = message
+            .channel_id
+            .delete_reaction(&context.http, message.id, None, ReactionType::Unicode(control.to_string()))
+            .await;
+        if let Err(why) = removed {
+            println!("Error removing chart controls: {:?}", why);
+        }
+    }
+}
+
+/// Says which symbols in a message couldn't be fetched, e.g. after a typo.
+async fn send_not_found(context: &Context, channel_id: ChannelId, errors: &[String]) {
+    let embed = CreateEmbed::new().description(errors.join("\n")).footer(CreateEmbedFooter::new(
+        "Crypto, futures and FX use Yahoo's symbols, e.g. $BTC-USD, $ES=F or $EURUSD=X",
+    ));
+    let sent = channel_id.send_message(&context.http, CreateMessage::new().embed(embed)).await;
+    if let Err(why) = sent {
+        println!("Error sending message: {:?}", why);
+    }
+}
+
+/// Summarizes several symbols in a single message, each over the time frame
+/// it asks for, fetching them all at once. `skipped` mentions past the cap
+/// are noted under the grid.
+async fn send_sparkline_grid(context: &Context, msg: &Message, requests: &[SymbolRequest<'_>], skipped: usize) {
+    let fetched = join_all(requests.iter().map(|r| stock::Stock::new(r.symbol, r.time_frame))).await;
+
+    let mut stocks = vec![];
+    let mut errors = vec![];
+    for result in fetched {
+        match result {
+            Ok(stock) => stocks.push(stock),
+            Err(why) => errors.push(why),
+        }
+    }
+    if !errors.is_empty() {
+        send_not_found(context, msg.channel_id, &errors).await;
+    }
+    if stocks.is_empty() {
+        return;
+    }
+
+    let grid = match plot::build_sparkline_grid(&stocks, theme_for(msg).await) {
+        Ok(grid) => grid,
+        Err(why) => {
+            println!("Error rendering sparklines: {:?}", why);
+            return;
+        }
+    };
+
+    let links = stocks
+        .iter()
+        .map(|s| format!("[{}]({})", s.symbol, symbols::yahoo_url(&s.symbol)))
+        .collect::<Vec<String>>()
+        .join(" | ");
+    let filename = "sparklines.png";
+    let mut embed = CreateEmbed::new().description(links).attachment(filename);
+    if skipped > 0 {
+        embed = embed.footer(CreateEmbedFooter::new(format!("Only the first {} symbols are shown", requests.len())));
+    }
+    let sent = msg
+        .channel_id
+        .send_message(
+            &context.http,
+            CreateMessage::new().embed(embed).add_file(CreateAttachment::bytes(grid, filename)),
+        )
+        .await;
+    if let Err(why) = sent {
+        println!("Error sending message: {:?}", why);
+    }
+}
+
 struct Handler;
 
 #[async_trait]
 impl EventHandler for Handler {
+    async fn ready(&self, context: Context, _ready: Ready) {
+        slash::register(&context.http).await;
+        if let Some(config) = scanner::ScannerConfig::from_env() {
+            scanner::spawn(context.http.clone(), config);
+        }
+        if let Some(schedule) = heatmap::HeatmapSchedule::from_env() {
+            heatmap::spawn(context.http.clone(), schedule);
+        }
+    }
+
+    async fn interaction_create(&self, context: Context, interaction: Interaction) {
+        slash::handle(&context, interaction).await;
+    }
+
     async fn message(&self, context: Context, msg: Message) {
+        if msg.content.starts_with(PREFIX) {
+            return;
+        }
+
         if (msg.author.name != "Stout") {
-            let symbol_names = get_symbol_names(&msg.content);
-
-            let stocks = symbol_names.into_iter().map(|x| stock::Stock::new(&x));
-
-            for handle in stocks {
-
-                let stock = handle.await;
-                /*
-                plot::build_chart(&stock).unwrap();
-                let base64 = utils::read_image("./0.png")
-                    .expect("Failed to read image");
-                let img = base64;
-                */
-                //println!("{:?}", img);
-
-                let company_website = stock.company.to_owned().map_or_else(|| None, |v| v.website);
-                let company_website = company_website.map_or_else(|| "".to_string(), |w| format!(" | [web]({})", w));
-                let msg = msg
-                    .channel_id
-                    .send_message(&context.http, |m| {
-                        m.embed(|e| { 
-                            e.title(format!("{} - 24hrs", stock.symbol));
-                            e.fields(vec![
-                                ("Price".to_string(), format!("${: <7.2}", stock.current_price), true),
-                                ("Cap".to_string(), format!("{}", common::format_large_number(stock.market_cap.unwrap_or(0.0) as f64)), true),
-                                ("Change".to_string(), format!("{:.2}%", stock.pct_change * 100.0), true),
-                            ]);
-                            e.fields(vec![
-                                ("Low".to_string(), format!("${: <7.2}", stock.low), true),
-                                ('\u{200B}'.to_string(), '\u{200B}'.to_string(), true),
-                                ("High".to_string(), format!("${: <7.2}", stock.high), true),
-                            ]);
-                            e.description(format!("[twits](https://stocktwits.com/symbol/{}) | [yhoo](https://finance.yahoo.com/quote/{}/){}", stock.symbol, stock.symbol, company_website));
-
-                            //e.image(AttachmentType::Image(img));
-                            e
-                        });
-
-                        m
-                    })
-                    .await;
-                if let Err(why) = msg {
-                    println!("Error sending message: {:?}", why);
+            let (requests, skipped) = limit_requests(parse_symbol_requests(&msg.content), ratelimit::max_symbols());
+            if requests.is_empty() || !within_limits(&context, &msg, requests.len()).await {
+                return;
+            }
+            if requests.len() >= SPARKLINE_MIN_SYMBOLS {
+                send_sparkline_grid(&context, &msg, &requests, skipped).await;
+                return;
+            }
+
+            for request in requests {
+                match stock::Stock::new(request.symbol, request.time_frame).await {
+                    Ok(stock) => send_chart(&context, &msg, &stock, request.extended, request.chart_options).await,
+                    Err(why) => send_not_found(&context, msg.channel_id, &[why]).await,
                 }
             }
         }
@@ -92,13 +1025,27 @@ impl EventHandler for Handler {
 
 #[tokio::main]
 async fn main() {
-    let framework = StandardFramework::new()
-        .configure(|c| c.prefix("~")) // set the bot's prefix to "~"
-        .group(&GENERAL_GROUP);
+    // The `~` commands run on serenity's standard framework, which 0.12
+    // deprecates without a replacement for prefix commands.
+    #[allow(deprecated)]
+    let framework = {
+        let framework = StandardFramework::new()
+            .before(before)
+            .help(&HELP)
+            .group(&GENERAL_GROUP);
+        framework.configure(Configuration::new().prefix(PREFIX)); // set the bot's prefix to "~"
+        framework
+    };
+
+    // Symbol mentions are read out of message text, which is a privileged
+    // intent that has to be enabled for the bot too.
+    let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
+
+    storage::open_from_env().expect("Err opening database");
 
     // Login with a bot token from the environment
     let token = env::var("DISCORD_TOKEN").expect("token");
-    let mut client = Client::builder(&token)
+    let mut client = Client::builder(&token, intents)
         .event_handler(Handler)
         .framework(framework)
         .await
@@ -110,25 +1057,248 @@ async fn main() {
     }
 }
 
-fn get_symbol_names(message: &str) -> Vec<&str> {
-    return SYMBOL_RE
+/// What an inline `$SYMBOL` mention asks for: `$AAPL+` for the extended
+/// embed, and a time frame, chart type, price scale or indicators either
+/// after colons, e.g. `$AAPL:1Y` or `$AAPL:3M:candle`, or as the words that
+/// follow, e.g. `$AAPL 3M candle sma50`.
+#[derive(Debug, Clone)]
+struct SymbolRequest<'a> {
+    symbol: &'a str,
+    extended: bool,
+    time_frame: TimeFrame,
+    chart_options: plot::ChartOptions,
+}
+
+/// Drops repeated mentions of a symbol, counting aliases as the symbol they
+/// stand for, and caps what's left at `max`. Also returns how many were cut
+/// by the cap.
+fn limit_requests(requests: Vec<SymbolRequest<'_>>, max: usize) -> (Vec<SymbolRequest<'_>>, usize) {
+    let mut seen = HashSet::new();
+    let mut requests = requests
+        .into_iter()
+        .filter(|r| seen.insert(symbols::resolve(r.symbol)))
+        .collect::<Vec<SymbolRequest>>();
+
+    let skipped = requests.len().saturating_sub(max);
+    requests.truncate(max);
+    (requests, skipped)
+}
+
+fn parse_symbol_requests(message: &str) -> Vec<SymbolRequest<'_>> {
+    SYMBOL_RE
         .captures_iter(message)
-        .map(|x| x.get(2).unwrap_or(None).as_str())
-        .collect::<Vec<&str>>();
+        .map(|captures| {
+            let modifiers = captures.get(3).expect("modifiers always match");
+            let words = modifiers
+                .as_str()
+                .split(':')
+                .filter(|m| !m.is_empty())
+                .chain(message[modifiers.end()..].split_whitespace());
+            let (time_frame, chart_options, _) = read_chart_options(words);
+
+            SymbolRequest {
+                symbol: captures.get(1).expect("symbol always matches").as_str(),
+                extended: captures.get(2).is_some(),
+                time_frame,
+                chart_options,
+            }
+        })
+        .collect()
+}
+
+/// Reads an optional time frame, chart type, price scale and indicators in any
+/// order, stopping at the first word that isn't one of them, which is also
+/// returned.
+fn read_chart_options<'a>(words: impl Iterator<Item = &'a str>) -> (TimeFrame, plot::ChartOptions, Option<&'a str>) {
+    let mut time_frame = TimeFrame::Day1;
+    let mut chart_type = ChartType::Line;
+    let mut price_scale = None;
+    let mut format = plot::ChartFormat::Png;
+    let mut indicators = vec![];
+    let mut unknown = None;
+
+    for word in words {
+        if let Ok(t) = word.parse() {
+            time_frame = t;
+        } else if let Ok(c) = word.parse() {
+            chart_type = c;
+        } else if let Ok(s) = word.parse() {
+            price_scale = Some(s);
+        } else if let Ok(f) = word.parse() {
+            format = f;
+        } else if let Ok(i) = word.parse::<plot::Indicator>() {
+            if !indicators.contains(&i) {
+                indicators.push(i);
+            }
+        } else {
+            unknown = Some(word);
+            break;
+        }
+    }
+
+    let defaults = plot::ChartOptions::for_time_frame(time_frame);
+    let options = plot::ChartOptions {
+        chart_type,
+        price_scale: price_scale.unwrap_or(defaults.price_scale),
+        format,
+        indicators,
+        ..defaults
+    };
+    (time_frame, options, unknown)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    fn request<'a>(message: &'a str, symbol: &str) -> SymbolRequest<'a> {
+        parse_symbol_requests(message).into_iter().find(|r| r.symbol == symbol).unwrap()
+    }
+
     #[test]
     fn it_pulls_correct_symbol_names() {
+        let symbol_names = |message| parse_symbol_requests(message).into_iter().map(|r| r.symbol).collect::<Vec<&str>>();
+
         let message = "This is a $TEST $20.20 to $ASDFAA $asdf see if $TICKS names $N works.";
-        assert_eq!(get_symbol_names(&message), ["TEST", "TICKS", "N"]);
+        assert_eq!(symbol_names(message), ["TEST", "TICKS", "N"]);
 
         let message = "$TEST";
-        assert_eq!(get_symbol_names(&message), ["TEST"]);
+        assert_eq!(symbol_names(message), ["TEST"]);
 
         let message = "this is a test $TEST+";
-        assert_eq!(get_symbol_names(&message), ["TEST+"]);
+        assert_eq!(symbol_names(message), ["TEST"]);
+        assert!(request(message, "TEST").extended);
+        assert!(!request("$TEST", "TEST").extended);
+
+        let message = "$BTC-USD, $ES=F and $EURUSD=X vs $^GSPC, $RY.TO and $BRK.B. Also $AAPL.";
+        assert_eq!(symbol_names(message), ["BTC-USD", "ES=F", "EURUSD=X", "^GSPC", "RY.TO", "BRK.B", "AAPL"]);
+
+        let message = "$ES=F:1W and $^VIX+";
+        assert_eq!(request(message, "ES=F").time_frame, TimeFrame::Week1);
+        assert!(request(message, "^VIX").extended);
+    }
+
+    #[test]
+    fn it_drops_repeated_and_excess_symbols() {
+        let symbols = |message| {
+            let (requests, skipped) = limit_requests(parse_symbol_requests(message), 12);
+            (requests.into_iter().map(|r| r.symbol).collect::<Vec<&str>>(), skipped)
+        };
+
+        assert_eq!(symbols("$SPX $AAPL:1Y $^GSPC $AAPL $TSLA"), (vec!["SPX", "AAPL", "TSLA"], 0));
+
+        let message = (b'A'..=b'N').map(|c| format!("${} ", c as char)).collect::<String>();
+        let (kept, skipped) = symbols(&message);
+        assert_eq!(kept.len(), 12);
+        assert_eq!(kept[0], "A");
+        assert_eq!(skipped, 2);
+    }
+
+    #[test]
+    fn it_parses_inline_modifiers() {
+        use plot::{Indicator, PriceScale};
+
+        let parsed = |message| {
+            let r = request(message, "AAPL");
+            (r.extended, r.time_frame, r.chart_options.chart_type)
+        };
+        assert_eq!(parsed("$AAPL:1Y"), (false, TimeFrame::Year1, ChartType::Line));
+        assert_eq!(parsed("look at $AAPL:3M:candle today"), (false, TimeFrame::Month3, ChartType::Candle));
+        assert_eq!(parsed("$AAPL+:6M:ohlc."), (true, TimeFrame::Month6, ChartType::Ohlc));
+        assert_eq!(parsed("$AAPL:bogus 1W"), (false, TimeFrame::Day1, ChartType::Line));
+
+        let r = request("$AAPL:5Y:pct rsi", "AAPL");
+        assert_eq!(r.chart_options.price_scale, PriceScale::Percent);
+        assert_eq!(r.chart_options.indicators, [Indicator::Rsi(14)]);
+
+        let requests = parse_symbol_requests("$AAPL:1Y, $TSLA:1W and $SPY");
+        assert_eq!(
+            requests.iter().map(|r| (r.symbol, r.time_frame)).collect::<Vec<(&str, TimeFrame)>>(),
+            [("AAPL", TimeFrame::Year1), ("TSLA", TimeFrame::Week1), ("SPY", TimeFrame::Day1)]
+        );
+    }
+
+    #[test]
+    fn it_reads_chart_options_after_a_symbol() {
+        let chart_type = |message, symbol| {
+            let r = request(message, symbol);
+            (r.time_frame, r.chart_options.chart_type)
+        };
+
+        let message = "what about $AAPL 3M candle and $TSLA";
+        assert_eq!(chart_type(message, "AAPL"), (TimeFrame::Month3, ChartType::Candle));
+        assert_eq!(chart_type(message, "TSLA"), (TimeFrame::Day1, ChartType::Line));
+
+        let message = "$SPY+ ohlc 1W";
+        assert_eq!(chart_type(message, "SPY"), (TimeFrame::Week1, ChartType::Ohlc));
+
+        let message = "$SPY is up 1M shares";
+        assert_eq!(chart_type(message, "SPY"), (TimeFrame::Day1, ChartType::Line));
+    }
+
+    #[test]
+    fn it_steps_time_frames_with_controls() {
+        let step = |emoji: &str| control_step(&ReactionType::Unicode(emoji.to_string())).map(|step| step(TimeFrame::Day1));
+        assert_eq!(step(CONTROLS[0]), Some(TimeFrame::Year5));
+        assert_eq!(step(CONTROLS[1]), Some(TimeFrame::Week1));
+        assert_eq!(step("▶"), Some(TimeFrame::Week1));
+        assert_eq!(step("👍"), None);
+    }
+
+    #[test]
+    fn it_reads_price_scales_after_a_symbol() {
+        use plot::PriceScale;
+
+        let price_scale = |message| request(message, "SPY").chart_options.price_scale;
+        assert_eq!(price_scale("$SPY 5Y"), PriceScale::Log);
+        assert_eq!(price_scale("$SPY 5Y linear"), PriceScale::Linear);
+        assert_eq!(price_scale("$SPY pct 1Y candle"), PriceScale::Percent);
+        assert_eq!(price_scale("$SPY 1Y"), PriceScale::Linear);
+    }
+
+    #[test]
+    fn it_reads_indicators_after_a_symbol() {
+        use plot::Indicator;
+
+        let r = request("$TSLA 6M sma50 sma200 bb rsi macd rsi", "TSLA");
+        assert_eq!(r.time_frame, TimeFrame::Month6);
+        assert_eq!(
+            r.chart_options.indicators,
+            [
+                Indicator::Sma(50),
+                Indicator::Sma(200),
+                Indicator::Bollinger(20),
+                Indicator::Rsi(14),
+                Indicator::Macd,
+            ]
+        );
+
+        let r = request("$TSLA sma1 is too short", "TSLA");
+        assert!(r.chart_options.indicators.is_empty());
+    }
+
+    #[test]
+    fn it_stops_chart_arguments_at_unknown_words() {
+        let (time_frame, options, unknown) = read_chart_options("1Y candle 3mo sma50".split_whitespace());
+        assert_eq!(time_frame, TimeFrame::Year1);
+        assert_eq!(options.chart_type, ChartType::Candle);
+        assert!(options.indicators.is_empty());
+        assert_eq!(unknown, Some("3mo"));
+
+        assert_eq!(read_chart_options("3M ohlc log".split_whitespace()).2, None);
+    }
+
+    #[test]
+    fn it_reads_the_chart_format() {
+        assert_eq!(read_chart_options("3M".split_whitespace()).1.format, plot::ChartFormat::Png);
+        assert_eq!(read_chart_options("SVG 3M".split_whitespace()).1.format, plot::ChartFormat::Svg);
+        assert_eq!(request("$SPY candle svg", "SPY").chart_options.format, plot::ChartFormat::Svg);
+    }
+
+    #[test]
+    fn it_truncates_descriptions_between_words() {
+        assert_eq!(truncate_words("Designs phones.", 20), "Designs phones.");
+        assert_eq!(truncate_words("Designs phones and computers.", 20), "Designs phones and…");
+        assert!(truncate_words(&"word ".repeat(200), INFO_DESCRIPTION_CHARS).chars().count() <= INFO_DESCRIPTION_CHARS);
     }
 }
@@ -0,0 +1,309 @@
+use crate::api::model::{OptionsContract, OptionsData, OptionsHeader};
+use chrono::{TimeZone, Utc};
+use futures::stream::{self, StreamExt};
+use std::fmt;
+use std::io;
+
+/// Standard equity option contract multiplier.
+pub const CONTRACT_SIZE: f64 = 100.0;
+/// How many expirations of a chain are fetched at once.
+const MAX_CONCURRENT_EXPIRATIONS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContractKind {
+    Call,
+    Put,
+}
+
+impl fmt::Display for ContractKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ContractKind::Call => "C",
+            ContractKind::Put => "P",
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
+/// Dollar value traded in a contract today, from its last price and volume.
+pub fn premium_traded(contract: &OptionsContract) -> f64 {
+    contract.volume.unwrap_or(0) as f64 * contract.last_price * CONTRACT_SIZE
+}
+
+pub fn format_expiration(expiration_date: i64) -> String {
+    Utc.timestamp(expiration_date, 0).format("%F").to_string()
+}
+
+/// Mid of the quoted market when both sides are present, otherwise the last trade.
+pub fn contract_price(contract: &OptionsContract) -> f64 {
+    match (contract.bid, contract.ask) {
+        (Some(bid), Some(ask)) if bid > 0.0 && ask > 0.0 => (bid + ask) / 2.0,
+        _ => contract.last_price,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpliedMove {
+    pub expiration_date: i64,
+    pub strike: f64,
+    /// Price of the at-the-money straddle, i.e. the expected move in dollars.
+    pub straddle: f64,
+    /// The straddle price as a fraction of the underlying price.
+    pub pct: f64,
+}
+
+/// Returns the call and put at the strike closest to the underlying price that
+/// lists both.
+fn atm_contracts(underlying_price: f64, data: &OptionsData) -> Option<(&OptionsContract, &OptionsContract)> {
+    data.calls
+        .iter()
+        .filter_map(|call| {
+            data.puts
+                .iter()
+                .find(|put| put.strike == call.strike)
+                .map(|put| (call, put))
+        })
+        .min_by(|(a, _), (b, _)| {
+            let a = (a.strike - underlying_price).abs();
+            let b = (b.strike - underlying_price).abs();
+            a.total_cmp(&b)
+        })
+}
+
+/// Computes the expected move through expiry from the at-the-money straddle.
+pub fn implied_move(underlying_price: f64, data: &OptionsData) -> Option<ImpliedMove> {
+    let (call, put) = atm_contracts(underlying_price, data)?;
+
+    let straddle = contract_price(call) + contract_price(put);
+    if underlying_price <= 0.0 || straddle <= 0.0 {
+        return None;
+    }
+
+    Some(ImpliedMove {
+        expiration_date: data.expiration_date,
+        strike: call.strike,
+        straddle,
+        pct: straddle / underlying_price,
+    })
+}
+
+/// Yahoo reports near-zero volatility for contracts it can't solve for, so
+/// anything below this is treated as missing.
+const MIN_IMPLIED_VOLATILITY: f64 = 0.01;
+
+fn implied_volatility(contract: &OptionsContract) -> Option<f64> {
+    contract
+        .implied_volatility
+        .filter(|iv| *iv >= MIN_IMPLIED_VOLATILITY)
+}
+
+/// Returns `(strike, implied volatility)` points ordered by strike.
+pub fn iv_smile(contracts: &[OptionsContract]) -> Vec<(f64, f64)> {
+    let mut points = contracts
+        .iter()
+        .filter_map(|c| implied_volatility(c).map(|iv| (c.strike, iv)))
+        .collect::<Vec<(f64, f64)>>();
+
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+    points
+}
+
+/// Average implied volatility of the at-the-money call and put.
+pub fn atm_iv(underlying_price: f64, data: &OptionsData) -> Option<f64> {
+    let (call, put) = atm_contracts(underlying_price, data)?;
+
+    match (implied_volatility(call), implied_volatility(put)) {
+        (Some(call), Some(put)) => Some((call + put) / 2.0),
+        (call, put) => call.or(put),
+    }
+}
+
+/// Returns `(expiration date, ATM implied volatility)` points for each
+/// expiration in `chain`, ordered by date.
+pub fn term_structure(chain: &[OptionsHeader]) -> Vec<(i64, f64)> {
+    let mut points = chain
+        .iter()
+        .flat_map(|header| {
+            let underlying_price = header.quote.regular_market_price;
+            header
+                .options
+                .iter()
+                .filter_map(move |data| atm_iv(underlying_price, data).map(|iv| (data.expiration_date, iv)))
+        })
+        .collect::<Vec<(i64, f64)>>();
+
+    points.sort_by_key(|p| p.0);
+    points
+}
+
+/// Returns the first expiration after `date`, so that its options price in
+/// whatever happens on that date.
+pub fn expiration_after(expiration_dates: &[i64], date: i64) -> Option<i64> {
+    expiration_dates.iter().copied().filter(|d| *d > date).min()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExpectedMoves {
+    pub nearest: Option<ImpliedMove>,
+    pub earnings_date: Option<i64>,
+    pub earnings: Option<ImpliedMove>,
+}
+
+async fn fetch_implied_move(symbol: &str, expiration_date: i64) -> Result<Option<ImpliedMove>, io::Error> {
+    let header = crate::CLIENT
+        .get_options_for_expiration_date(symbol, expiration_date)
+        .await
+        .map_err(io::Error::other)?;
+
+    Ok(header
+        .options
+        .first()
+        .and_then(|data| implied_move(header.quote.regular_market_price, data)))
+}
+
+/// Fetches the implied move for the nearest expiration and for the first
+/// expiration after the next earnings date.
+pub async fn fetch_expected_moves(symbol: &str) -> Result<ExpectedMoves, io::Error> {
+    let expiration_dates = crate::CLIENT
+        .get_options_expiration_dates(symbol)
+        .await
+        .map_err(io::Error::other)?;
+
+    let company = crate::CLIENT
+        .get_company_data(symbol)
+        .await
+        .map_err(io::Error::other)?;
+
+    let now = Utc::now().timestamp();
+    let earnings_date = company
+        .calendar_events
+        .and_then(|c| c.earnings)
+        .and_then(|e| e.earnings_date.into_iter().map(|d| d.date).find(|d| *d >= now));
+
+    let nearest = match expiration_dates.iter().min() {
+        Some(date) => fetch_implied_move(symbol, *date).await?,
+        None => None,
+    };
+
+    let earnings = match earnings_date.and_then(|d| expiration_after(&expiration_dates, d)) {
+        Some(date) => fetch_implied_move(symbol, date).await?,
+        None => None,
+    };
+
+    Ok(ExpectedMoves {
+        nearest,
+        earnings_date,
+        earnings,
+    })
+}
+
+/// Fetches the option chain for every listed expiration of `symbol`, a few
+/// expirations at a time. Expirations that fail are left out; only if they
+/// all do is it an error.
+pub async fn fetch_option_chain(symbol: &str) -> Result<Vec<OptionsHeader>, io::Error> {
+    let expiration_dates = crate::CLIENT
+        .get_options_expiration_dates(symbol)
+        .await
+        .map_err(io::Error::other)?;
+
+    let fetched = stream::iter(expiration_dates.iter().copied())
+        .map(|expiration_date| async move {
+            let header = crate::CLIENT.get_options_for_expiration_date(symbol, expiration_date).await;
+            (expiration_date, header)
+        })
+        .buffered(MAX_CONCURRENT_EXPIRATIONS)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut chain = Vec::with_capacity(fetched.len());
+    let mut last_error = None;
+    for (expiration_date, header) in fetched {
+        match header {
+            Ok(header) => chain.push(header),
+            Err(why) => {
+                println!("Error fetching {} options expiring {}: {:?}", symbol, expiration_date, why);
+                last_error = Some(why);
+            }
+        }
+    }
+
+    match last_error {
+        Some(why) if chain.is_empty() => Err(io::Error::other(why)),
+        _ => Ok(chain),
+    }
+}
+
+#[cfg(test)]
+pub fn fixture(json: &str) -> OptionsHeader {
+    serde_json::from_str(json).expect("Failed to parse options fixture")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spy() -> OptionsHeader {
+        fixture(include_str!("../fixtures/options_spy.json"))
+    }
+
+    #[test]
+    fn it_prices_contracts_from_the_mid() {
+        let header = spy();
+        let call = &header.options[0].calls[2];
+        assert!((contract_price(call) - 5.8).abs() < 1e-9);
+
+        let mut call = call.clone();
+        call.bid = Some(0.0);
+        assert!((contract_price(&call) - call.last_price).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_computes_the_atm_straddle() {
+        let header = spy();
+        let implied = implied_move(header.quote.regular_market_price, &header.options[0]).unwrap();
+
+        assert_eq!(implied.expiration_date, 1616112000);
+        assert_eq!(implied.strike, 390.0);
+        assert!((implied.straddle - 11.4).abs() < 1e-9);
+        assert!((implied.pct - 11.4 / 389.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_builds_the_iv_smile() {
+        let header = spy();
+        let mut calls = header.options[0].calls.clone();
+        calls[0].implied_volatility = Some(0.00001);
+        calls.swap(1, 3);
+
+        let smile = iv_smile(&calls);
+        assert_eq!(
+            smile,
+            [(385.0, 0.222), (390.0, 0.205), (395.0, 0.191), (400.0, 0.183)]
+        );
+    }
+
+    #[test]
+    fn it_computes_atm_iv_term_structure() {
+        let header = spy();
+        let iv = atm_iv(header.quote.regular_market_price, &header.options[0]).unwrap();
+        assert!((iv - 0.217).abs() < 1e-9);
+
+        let mut later = header.clone();
+        later.options[0].expiration_date = 1616716800;
+        later.options[0].puts[2].implied_volatility = None;
+
+        let points = term_structure(&[later, header]);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].0, 1616112000);
+        assert_eq!(points[1], (1616716800, 0.205));
+    }
+
+    #[test]
+    fn it_picks_the_expiration_after_a_date() {
+        let dates = [1617321600, 1616112000, 1616716800];
+        assert_eq!(expiration_after(&dates, 1616000000), Some(1616112000));
+        assert_eq!(expiration_after(&dates, 1616112000), Some(1616716800));
+        assert_eq!(expiration_after(&dates, 1617321600), None);
+    }
+}
@@ -1,29 +1,1939 @@
 use plotters::prelude::*;
+use plotters::coord::ranged1d::{KeyPointHint, NoDefaultFormatting, Ranged, ValueFormatter};
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
+use plotters::drawing::DrawingAreaErrorKind;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+use image::png::PngEncoder;
+use image::ColorType;
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::api::model::ChartCurrentTradingPeriod;
+use super::compare::Comparison;
+use super::events::{Event, EventKind};
+use super::heatmap::{self, Rect, Tile};
+use super::common::{format_large_number, MarketHours, Price, TimeFrame, TradingPeriod};
+use super::indicators;
 use super::stock::Stock;
+use super::options;
+use super::strategy::Strategy;
+use super::symbols;
+use super::theme::{Palette, Theme};
 
+/// Sized to fill the width of a Discord embed without being scaled down.
+pub const CHART_SIZE: (u32, u32) = (600, 300);
+pub const OPTIONS_CHART_SIZE: (u32, u32) = (800, 400);
 
-pub fn build_chart(stock: &Stock) -> Result<(), Box<dyn std::error::Error>>  {
-    let root = BitMapBackend::new("0.png", (200, 100)).into_drawing_area();
-    root.fill(&WHITE)?;
-    let mut chart = ChartBuilder::on(&root)
-        .margin(5)
-        .x_label_area_size(30)
-        .y_label_area_size(30)
-        .build_cartesian_2d(stock.prices[0].date as f32..stock.prices.last().unwrap().date as f32, stock.low as f32..stock.high as f32)?;
-        chart.configure_mesh().draw()?;
+/// Height of the price and volume panes; each indicator pane adds its own.
+const PRICE_PANE_HEIGHT: u32 = 225;
+const VOLUME_PANE_HEIGHT: u32 = 40;
+const INDICATOR_PANE_HEIGHT: u32 = 80;
+const X_LABEL_AREA_SIZE: u32 = 25;
+pub const MAX_INDICATOR_PANES: usize = 3;
+const EVENT_MARKER_RADIUS: i32 = 7;
+
+/// Two cells side by side fill the width of an embed.
+const SPARKLINE_CELL_SIZE: (u32, u32) = (300, 60);
+const SPARKLINE_COLUMNS: usize = 2;
+const SPARKLINE_LABEL_WIDTH: u32 = 130;
+
+pub const HEATMAP_SIZE: (u32, u32) = (800, 500);
+const HEATMAP_HEADER_HEIGHT: f64 = 16.0;
+/// Daily moves of this size or more get the strongest shade.
+const HEATMAP_FULL_CHANGE: f64 = 0.03;
+
+/// A replay fills in over this many frames, then holds for a few more.
+const REPLAY_FRAMES: usize = 60;
+const REPLAY_HOLD_FRAMES: usize = 15;
+const REPLAY_FRAME_DELAY_MS: u32 = 80;
+
+/// Output format for rendered charts. Discord only previews bitmaps inline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartFormat {
+    Png,
+    Svg,
+}
+
+impl ChartFormat {
+    pub fn filename(self, name: &str) -> String {
+        match self {
+            ChartFormat::Png => format!("{}.png", name),
+            ChartFormat::Svg => format!("{}.svg", name),
+        }
+    }
+}
+
+impl FromStr for ChartFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(ChartFormat::Png),
+            "svg" => Ok(ChartFormat::Svg),
+            _ => Err("Valid chart formats are: 'png', 'svg'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChartType {
+    Line,
+    Candle,
+    Ohlc,
+}
+
+impl FromStr for ChartType {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ChartType::*;
+
+        match s.to_lowercase().as_str() {
+            "line" => Ok(Line),
+            "candle" => Ok(Candle),
+            "ohlc" => Ok(Ohlc),
+            _ => Err("Valid chart types are: 'line', 'candle', 'ohlc'"),
+        }
+    }
+}
+
+/// A study drawn over the price pane or in its own pane below the volume.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Indicator {
+    Sma(usize),
+    Ema(usize),
+    Wma(usize),
+    Vwap,
+    Bollinger(usize),
+    Rsi(usize),
+    Macd,
+    Atr(usize),
+    Stochastic(usize),
+    Obv,
+}
+
+impl Indicator {
+    /// Whether the indicator shares the price scale, rather than needing a pane.
+    pub fn is_overlay(self) -> bool {
+        matches!(
+            self,
+            Indicator::Sma(_) | Indicator::Ema(_) | Indicator::Wma(_) | Indicator::Vwap | Indicator::Bollinger(_)
+        )
+    }
+
+    fn label(self) -> String {
+        match self {
+            Indicator::Sma(period) => format!("SMA {}", period),
+            Indicator::Ema(period) => format!("EMA {}", period),
+            Indicator::Wma(period) => format!("WMA {}", period),
+            Indicator::Vwap => "VWAP".to_string(),
+            Indicator::Bollinger(period) => format!("BB {}, 2", period),
+            Indicator::Rsi(period) => format!("RSI {}", period),
+            Indicator::Macd => "MACD 12, 26, 9".to_string(),
+            Indicator::Atr(period) => format!("ATR {}", period),
+            Indicator::Stochastic(period) => format!("Stoch {}, 3", period),
+            Indicator::Obv => "OBV".to_string(),
+        }
+    }
+}
+
+impl FromStr for Indicator {
+    type Err = &'static str;
+
+    /// Parses names like `sma50`, `ema`, `bb`, `rsi` or `stoch`, with an
+    /// optional period.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Indicator::*;
+
+        let s = s.to_lowercase();
+        let split = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let (name, period) = s.split_at(split);
+        let period = match period {
+            "" => None,
+            p => match p.parse::<usize>() {
+                Ok(p) if p > 1 && p <= 500 => Some(p),
+                _ => return Err("Indicator periods must be between 2 and 500"),
+            },
+        };
+
+        match (name, period) {
+            ("sma", p) => Ok(Sma(p.unwrap_or(20))),
+            ("ema", p) => Ok(Ema(p.unwrap_or(20))),
+            ("wma", p) => Ok(Wma(p.unwrap_or(20))),
+            ("bb", p) => Ok(Bollinger(p.unwrap_or(20))),
+            ("rsi", p) => Ok(Rsi(p.unwrap_or(14))),
+            ("atr", p) => Ok(Atr(p.unwrap_or(14))),
+            ("stoch", p) => Ok(Stochastic(p.unwrap_or(14))),
+            ("vwap", None) => Ok(Vwap),
+            ("macd", None) => Ok(Macd),
+            ("obv", None) => Ok(Obv),
+            _ => Err("Valid indicators are: 'sma', 'ema', 'wma', 'vwap', 'bb', 'rsi', 'macd', 'atr', 'stoch', 'obv'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeAxis {
+    /// Bars are placed by timestamp, leaving gaps overnight and on weekends.
+    Continuous,
+    /// Bars are placed side by side, skipping time when the market is closed.
+    Gapless,
+}
+
+impl TimeAxis {
+    /// A single session has no closed hours to skip, so it keeps clock time.
+    pub fn default_for(time_frame: TimeFrame) -> TimeAxis {
+        match time_frame {
+            TimeFrame::Day1 => TimeAxis::Continuous,
+            _ => TimeAxis::Gapless,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PriceScale {
+    Linear,
+    /// Equal distances are equal percentage moves, so decades of growth stay readable.
+    Log,
+    /// Labelled as the change from the first bar's close.
+    Percent,
+}
+
+impl PriceScale {
+    /// Over five years a linear scale flattens everything before the latest run-up.
+    pub fn default_for(time_frame: TimeFrame) -> PriceScale {
+        match time_frame {
+            TimeFrame::Year5 => PriceScale::Log,
+            _ => PriceScale::Linear,
+        }
+    }
+}
+
+impl FromStr for PriceScale {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use PriceScale::*;
+
+        match s.to_lowercase().as_str() {
+            "linear" | "lin" => Ok(Linear),
+            "log" => Ok(Log),
+            "percent" | "pct" | "%" => Ok(Percent),
+            _ => Err("Valid price scales are: 'linear', 'log', 'pct'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChartOptions {
+    pub chart_type: ChartType,
+    pub format: ChartFormat,
+    pub time_axis: TimeAxis,
+    pub price_scale: PriceScale,
+    pub indicators: Vec<Indicator>,
+    pub theme: Theme,
+}
+
+impl ChartOptions {
+    pub fn for_time_frame(time_frame: TimeFrame) -> ChartOptions {
+        ChartOptions {
+            chart_type: ChartType::Line,
+            format: ChartFormat::Png,
+            time_axis: TimeAxis::default_for(time_frame),
+            price_scale: PriceScale::default_for(time_frame),
+            indicators: vec![],
+            theme: Theme::default(),
+        }
+    }
+
+    /// The same options for another time frame, with its default axes.
+    pub fn with_time_frame(self, time_frame: TimeFrame) -> ChartOptions {
+        ChartOptions {
+            time_axis: TimeAxis::default_for(time_frame),
+            price_scale: PriceScale::default_for(time_frame),
+            ..self
+        }
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (
+            CHART_SIZE.0,
+            CHART_SIZE.1 + self.panes().count() as u32 * INDICATOR_PANE_HEIGHT,
+        )
+    }
+
+    /// Indicators drawn in their own pane, in the order they were requested.
+    fn panes(&self) -> impl Iterator<Item = Indicator> + '_ {
+        self.indicators
+            .iter()
+            .copied()
+            .filter(|i| !i.is_overlay())
+            .take(MAX_INDICATOR_PANES)
+    }
+}
+
+pub fn build_chart(stock: &Stock, options: ChartOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let bars = chart_bars(stock, options.chart_type)?;
+    let size = options.size();
+    let shown = bars.len();
+
+    match options.format {
+        ChartFormat::Png => render_png(size, |root| Ok(draw_price_chart(root, stock, &options, &bars, shown)?)),
+        ChartFormat::Svg => render_svg(size, |root| Ok(draw_price_chart(root, stock, &options, &bars, shown)?))
+            .map(String::into_bytes),
+    }
+}
+
+/// Animates the chart filling in from left to right, then holds on the
+/// finished chart. The axes span every bar from the first frame.
+pub fn build_replay(stock: &Stock, options: ChartOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let bars = chart_bars(stock, options.chart_type)?;
+    let frames = replay_frames(bars.len(), REPLAY_FRAMES);
+    let palette = options.theme.palette();
+
+    render_gif(options.size(), REPLAY_FRAME_DELAY_MS, frames.len(), |root, frame| {
+        let shown = frames[frame];
+        draw_price_chart(root, stock, &options, &bars, shown)?;
+
+        let last = bars[shown - 1];
+        root.draw(&Text::new(
+            format!(
+                "{}  {}",
+                stock.time_frame.format_time(last.date),
+                symbols::format_price(last.close, stock.instrument, stock.currency.as_deref())
+            ),
+            (CHART_SIZE.0 as i32 - 12, 4),
+            palette.font(13).pos(Pos::new(HPos::Right, VPos::Top)),
+        ))?;
+        Ok(())
+    })
+}
+
+/// How many bars each frame of a replay shows, ending with a pause on all of them.
+fn replay_frames(bars: usize, frames: usize) -> Vec<usize> {
+    let mut shown = (1..=frames).map(|f| (bars * f).div_ceil(frames).max(1)).collect::<Vec<usize>>();
+    shown.dedup();
+    shown.extend(std::iter::repeat(bars).take(REPLAY_HOLD_FRAMES));
+    shown
+}
+
+/// Bars worth drawing. Yahoo reports missing bars as zeros, which would drag
+/// every series to the axis.
+fn chart_bars(stock: &Stock, chart_type: ChartType) -> Result<Vec<Price>, Box<dyn std::error::Error>> {
+    let bars = stock
+        .prices
+        .iter()
+        .filter(|p| p.close > 0.0)
+        .filter(|p| chart_type == ChartType::Line || (p.open > 0.0 && p.high > 0.0 && p.low > 0.0))
+        .copied()
+        .collect::<Vec<Price>>();
+
+    if bars.len() < 2 {
+        return Err(format!("Not enough price data to chart {}", stock.symbol).into());
+    }
+    Ok(bars)
+}
+
+/// Draws the price series above a volume histogram and any indicator panes,
+/// all sharing the same time axis. Only the first `shown` bars are drawn, but
+/// the axes always fit them all.
+fn draw_price_chart<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    stock: &Stock,
+    options: &ChartOptions,
+    bars: &[Price],
+    shown: usize,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let palette = options.theme.palette();
+    let color = palette.change(stock.pct_change);
+
+    let dates = bars.iter().map(|p| p.date).collect::<Vec<i64>>();
+    let mut scale = TimeScale::new(&dates, options.time_axis, stock.time_frame);
+    scale.shown = shown;
+    let xs = scale.visible();
+    let x_range = scale.range();
+    let tick_label = |x: &f64| scale.label(x);
+
+    let overlays = options
+        .indicators
+        .iter()
+        .copied()
+        .filter(|i| i.is_overlay())
+        .map(|i| (i, overlay_lines(i, bars)))
+        .collect::<Vec<(Indicator, Vec<Vec<Option<f64>>>)>>();
+    let overlay_values = overlays.iter().flat_map(|(_, lines)| lines.iter().flatten().flatten().copied());
+
+    let y_range = match options.chart_type {
+        ChartType::Line => price_range(
+            bars.iter()
+                .map(|p| p.close)
+                .chain(stock.previous_close)
+                .chain(overlay_values),
+            options.price_scale,
+        ),
+        _ => price_range(
+            bars.iter()
+                .flat_map(|p| vec![p.low, p.high])
+                .chain(stock.previous_close)
+                .chain(overlay_values),
+            options.price_scale,
+        ),
+    }
+    .unwrap();
+    let price_axis = PriceAxis::new(options.price_scale, y_range.clone(), bars[0].close);
+    let price_label = price_axis.clone();
+
+    root.fill(&palette.background)?;
+    let panes = options.panes().collect::<Vec<Indicator>>();
+    let mut breakpoints = vec![PRICE_PANE_HEIGHT, PRICE_PANE_HEIGHT + VOLUME_PANE_HEIGHT];
+    for _ in 1..panes.len() {
+        breakpoints.push(breakpoints[breakpoints.len() - 1] + INDICATOR_PANE_HEIGHT);
+    }
+    if panes.is_empty() {
+        breakpoints.pop();
+    }
+    let areas = root.split_by_breakpoints([] as [u32; 0], breakpoints);
+
+    let mut chart = ChartBuilder::on(&areas[0])
+        .margin(10)
+        .margin_bottom(0)
+        .x_label_area_size(0)
+        .y_label_area_size(50)
+        .build_cartesian_2d(scale.axis(), price_axis)?;
 
     chart
-        .draw_series(LineSeries::new(
-            stock.prices.iter().map(|x| (x.date as f32, x.close as f32)),
-            &RED,
-        ))?
-        .label("y = x^2")
-        .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &RED));
+        .configure_mesh()
+        .label_style(palette.font(12))
+        .y_labels(6)
+        .y_label_formatter(&|v| price_label.label(v))
+        .axis_style(&palette.foreground)
+        .bold_line_style(&palette.grid)
+        .light_line_style(&palette.background)
+        .draw()?;
+
+    if let Some(periods) = &stock.trading_periods {
+        chart.draw_series(extended_hours(bars, periods).into_iter().map(|(start, end)| {
+            Rectangle::new(
+                [
+                    (scale.xs[start] - scale.half_bar, y_range.start),
+                    (scale.xs[end] + scale.half_bar, y_range.end),
+                ],
+                palette.foreground.mix(0.07).filled(),
+            )
+        }))?;
+    }
+
+    if let Some(previous_close) = stock.previous_close {
+        chart.draw_series(dashes(x_range.clone(), 40).map(|(start, end)| {
+            PathElement::new(vec![(start, previous_close), (end, previous_close)], &palette.foreground.mix(0.4))
+        }))?;
+    }
+
+    let bar_width = (chart.plotting_area().dim_in_pixel().0 as f64 / bars.len() as f64 * 0.7).max(1.0);
+    let tick = scale.half_bar * 0.7;
+
+    match options.chart_type {
+        ChartType::Line => {
+            let closes = xs.iter().zip(bars).map(|(x, p)| (*x, p.close));
+            chart.draw_series(AreaSeries::new(closes.clone(), y_range.start, &color.mix(0.15)))?;
+            chart.draw_series(LineSeries::new(closes, color.stroke_width(2)))?;
+        }
+        ChartType::Candle => {
+            chart.draw_series(xs.iter().zip(bars).map(|(x, p)| {
+                CandleStick::new(
+                    *x,
+                    p.open,
+                    p.high,
+                    p.low,
+                    p.close,
+                    palette.up.filled(),
+                    palette.down.filled(),
+                    bar_width as u32,
+                )
+            }))?;
+        }
+        ChartType::Ohlc => {
+            chart.draw_series(xs.iter().zip(bars).flat_map(|(x, p)| {
+                let x = *x;
+                let color = palette.change(p.close - p.open);
+                vec![
+                    PathElement::new(vec![(x, p.low), (x, p.high)], color),
+                    PathElement::new(vec![(x - tick, p.open), (x, p.open)], color),
+                    PathElement::new(vec![(x, p.close), (x + tick, p.close)], color),
+                ]
+            }))?;
+        }
+    }
+
+    let market_hours = MarketHours::default();
+    let mut has_legend = false;
+    for (n, (indicator, lines)) in overlays.iter().enumerate() {
+        let color = palette.series(n);
+        // VWAP starts over every session, so don't join one day to the next.
+        let new_session = |i: usize| {
+            *indicator == Indicator::Vwap
+                && market_hours.trading_day(bars[i].date) != market_hours.trading_day(bars[i - 1].date)
+        };
+
+        let mut labelled = false;
+        for line in lines {
+            for run in runs(xs, line, new_session) {
+                let series = chart.draw_series(LineSeries::new(run, color))?;
+                if !labelled {
+                    series
+                        .label(indicator.label())
+                        .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color.stroke_width(2)));
+                    labelled = true;
+                }
+            }
+        }
+        has_legend |= labelled;
+    }
+
+    let markers = event_markers(&stock.events, &dates[..shown]);
+    let letter_style = ("sans-serif", 11)
+        .into_font()
+        .style(FontStyle::Bold)
+        .color(&palette.background)
+        .pos(Pos::new(HPos::Center, VPos::Center));
+    for kind in EventKind::ALL.iter().copied() {
+        if !markers.iter().any(|m| m.2 == kind) {
+            continue;
+        }
+
+        let color = event_color(palette, kind);
+        chart
+            .draw_series(markers.iter().filter(|m| m.2 == kind).map(|(i, stack, _)| {
+                let y = -(EVENT_MARKER_RADIUS + 2) - *stack as i32 * (EVENT_MARKER_RADIUS * 2 + 2);
+                EmptyElement::at((xs[*i], y_range.start))
+                    + Circle::new((0, y), EVENT_MARKER_RADIUS, color.filled())
+                    + Text::new(kind.letter(), (0, y), letter_style.clone())
+            }))?
+            .label(kind.as_str())
+            .legend(move |(x, y)| Circle::new((x + 7, y), 5, color.filled()));
+        has_legend = true;
+    }
+
+    if has_legend {
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .label_font(palette.font(12))
+            .background_style(&palette.background.mix(0.8))
+            .border_style(&palette.foreground.mix(0.3))
+            .draw()?;
+    }
+
+    let max_volume = bars.iter().map(|p| p.volume).max().unwrap_or(0).max(1);
+    let mut volume = ChartBuilder::on(&areas[1])
+        .margin(10)
+        .margin_top(0)
+        .margin_bottom(if panes.is_empty() { 10 } else { 0 })
+        .x_label_area_size(if panes.is_empty() { X_LABEL_AREA_SIZE } else { 0 })
+        .y_label_area_size(50)
+        .build_cartesian_2d(scale.axis(), 0..max_volume)?;
+
+    volume
+        .configure_mesh()
+        .disable_y_mesh()
+        .label_style(palette.font(12))
+        .y_labels(3)
+        .x_label_formatter(&tick_label)
+        .y_label_formatter(&|v| format_large_number(*v as f64))
+        .axis_style(&palette.foreground)
+        .bold_line_style(&palette.grid)
+        .light_line_style(&palette.background)
+        .draw()?;
+
+    volume.draw_series(xs.iter().zip(bars).map(|(x, p)| {
+        let color = palette.change(p.close - p.open);
+        Rectangle::new([(x - tick, 0), (x + tick, p.volume)], color.mix(0.5).filled())
+    }))?;
+
+    for (i, (pane, area)) in panes.iter().zip(&areas[2..]).enumerate() {
+        draw_indicator_pane(area, *pane, bars, &scale, palette, i == panes.len() - 1)?;
+    }
+    Ok(())
+}
+
+/// The lines an overlay draws on the price pane, one value per bar.
+fn overlay_lines(indicator: Indicator, bars: &[Price]) -> Vec<Vec<Option<f64>>> {
+    match indicator {
+        Indicator::Sma(period) => vec![indicators::sma(bars, period)],
+        Indicator::Ema(period) => vec![indicators::ema(bars, period)],
+        Indicator::Wma(period) => vec![indicators::wma(bars, period)],
+        Indicator::Vwap => vec![indicators::vwap(bars)],
+        Indicator::Bollinger(period) => {
+            let bands = indicators::bollinger(bars, period, 2.0);
+            vec![
+                bands.iter().map(|b| b.map(|b| b.upper)).collect(),
+                bands.iter().map(|b| b.map(|b| b.middle)).collect(),
+                bands.iter().map(|b| b.map(|b| b.lower)).collect(),
+            ]
+        }
+        Indicator::Rsi(_) | Indicator::Macd | Indicator::Atr(_) | Indicator::Stochastic(_) | Indicator::Obv => vec![],
+    }
+}
+
+/// Splits a series into runs of consecutive values, so that a line isn't drawn
+/// across warm-up gaps or wherever `new_run` says to start over.
+fn runs(xs: &[f64], values: &[Option<f64>], new_run: impl Fn(usize) -> bool) -> Vec<Vec<(f64, f64)>> {
+    let mut runs: Vec<Vec<(f64, f64)>> = vec![];
+    let mut in_run = false;
+
+    for (i, (x, value)) in xs.iter().zip(values).enumerate() {
+        match value {
+            Some(value) => {
+                if !in_run || new_run(i) {
+                    runs.push(vec![]);
+                }
+                runs.last_mut().unwrap().push((*x, *value));
+                in_run = true;
+            }
+            None => in_run = false,
+        }
+    }
+
+    runs
+}
+
+/// Draws an oscillator in its own pane below the volume.
+fn draw_indicator_pane<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    indicator: Indicator,
+    bars: &[Price],
+    scale: &TimeScale,
+    palette: &Palette,
+    x_labels: bool,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let unbounded = |line: Vec<Option<f64>>| {
+        let y_range = padded_range(line.iter().flatten().copied()).unwrap_or(0.0..1.0);
+        (vec![line], vec![], vec![], y_range)
+    };
+    let (lines, histogram, guides, y_range) = match indicator {
+        Indicator::Rsi(period) => (vec![indicators::rsi(bars, period)], vec![], vec![30.0, 70.0], 0.0..100.0),
+        Indicator::Stochastic(period) => {
+            let points = indicators::stochastic(bars, period, 3);
+            let k = points.iter().map(|p| p.map(|p| p.k)).collect();
+            let d = points.iter().map(|p| p.and_then(|p| p.d)).collect();
+            (vec![k, d], vec![], vec![20.0, 80.0], 0.0..100.0)
+        }
+        Indicator::Atr(period) => unbounded(indicators::atr(bars, period)),
+        Indicator::Obv => unbounded(indicators::obv(bars).into_iter().map(Some).collect()),
+        _ => {
+            let points = indicators::macd(bars, 12, 26, 9);
+            let macd = points.iter().map(|p| p.map(|p| p.macd)).collect::<Vec<Option<f64>>>();
+            let signal = points.iter().map(|p| p.and_then(|p| p.signal)).collect::<Vec<Option<f64>>>();
+            let histogram = points.iter().map(|p| p.and_then(|p| p.histogram)).collect::<Vec<Option<f64>>>();
+            let values = macd.iter().chain(&signal).chain(&histogram).flatten().copied();
+            let y_range = padded_range(values.chain(std::iter::once(0.0))).unwrap();
+            (vec![macd, signal], histogram, vec![0.0], y_range)
+        }
+    };
+
+    let mut chart = ChartBuilder::on(area)
+        .margin_left(10)
+        .margin_right(10)
+        .margin_top(5)
+        .margin_bottom(if x_labels { 10 } else { 0 })
+        .x_label_area_size(if x_labels { X_LABEL_AREA_SIZE } else { 0 })
+        .y_label_area_size(50)
+        .build_cartesian_2d(scale.axis(), y_range.clone())?;
 
     chart
-        .configure_series_labels()
-        .background_style(&WHITE.mix(0.8))
-        .border_style(&BLACK)
+        .configure_mesh()
+        .disable_y_mesh()
+        .label_style(palette.font(12))
+        .y_labels(3)
+        .x_label_formatter(&|x| scale.label(x))
+        .y_label_formatter(&|v| match indicator {
+            Indicator::Obv => format_large_number(*v),
+            _ => format!("{:.1}", v),
+        })
+        .axis_style(&palette.foreground)
+        .bold_line_style(&palette.grid)
+        .light_line_style(&palette.background)
         .draw()?;
+
+    for level in guides {
+        chart.draw_series(dashes(scale.range(), 40).map(|(start, end)| {
+            PathElement::new(vec![(start, level), (end, level)], &palette.foreground.mix(0.3))
+        }))?;
+    }
+
+    let tick = scale.half_bar * 0.7;
+    chart.draw_series(scale.visible().iter().zip(&histogram).filter_map(|(x, h)| {
+        let h = (*h)?;
+        let color = palette.change(h);
+        Some(Rectangle::new([(x - tick, 0.0), (x + tick, h)], color.mix(0.5).filled()))
+    }))?;
+
+    for (n, line) in lines.iter().enumerate() {
+        let color = palette.series(n);
+        for run in runs(scale.visible(), line, |_| false) {
+            chart.draw_series(LineSeries::new(run, color))?;
+        }
+    }
+
+    area.draw(&Text::new(
+        indicator.label(),
+        (65, 7),
+        palette.font(12).color(&palette.foreground.mix(0.6)),
+    ))?;
     Ok(())
 }
+
+/// Places each event on the bar it falls within, numbering events that share a
+/// bar so their markers can stack. Events before the first bar or after the
+/// last, such as upcoming earnings, are dropped.
+fn event_markers(events: &[Event], dates: &[i64]) -> Vec<(usize, usize, EventKind)> {
+    let end = match dates {
+        [.., before, last] => last + (last - before),
+        [last] => last + 1,
+        [] => return vec![],
+    };
+
+    let mut markers: Vec<(usize, usize, EventKind)> = vec![];
+    for event in events {
+        let i = dates.partition_point(|d| *d <= event.date);
+        if i == 0 || event.date >= end {
+            continue;
+        }
+
+        let stack = markers.iter().filter(|m| m.0 == i - 1).count();
+        markers.push((i - 1, stack, event.kind));
+    }
+    markers
+}
+
+fn event_color(palette: &'static Palette, kind: EventKind) -> &'static RGBColor {
+    match kind {
+        EventKind::Earnings => palette.series(1),
+        EventKind::Dividend => &palette.up,
+        EventKind::Split => palette.series(2),
+    }
+}
+
+/// Bar positions and labelled ticks shared by every pane of a price chart.
+struct TimeScale {
+    xs: Vec<f64>,
+    ticks: Vec<(f64, String)>,
+    /// Half the average bar spacing, so the outermost bars aren't clipped.
+    half_bar: f64,
+    /// How many bars, from the first, to draw.
+    shown: usize,
+}
+
+impl TimeScale {
+    fn new(dates: &[i64], time_axis: TimeAxis, time_frame: TimeFrame) -> TimeScale {
+        let xs = x_positions(dates, time_axis);
+        let ticks = x_ticks(dates, &xs, time_frame);
+        let half_bar = (xs[xs.len() - 1] - xs[0]) / (xs.len() - 1) as f64 / 2.0;
+
+        let shown = xs.len();
+        TimeScale {
+            xs,
+            ticks,
+            half_bar,
+            shown,
+        }
+    }
+
+    /// Positions of the bars being drawn.
+    fn visible(&self) -> &[f64] {
+        &self.xs[..self.shown]
+    }
+
+    fn range(&self) -> Range<f64> {
+        self.xs[0] - self.half_bar..self.xs[self.xs.len() - 1] + self.half_bar
+    }
+
+    fn axis(&self) -> TickAxis {
+        TickAxis::new(self.range(), &self.ticks)
+    }
+
+    fn label(&self, x: &f64) -> String {
+        self.ticks
+            .iter()
+            .find(|t| (t.0 - x).abs() < f64::EPSILON)
+            .map_or_else(String::new, |t| t.1.clone())
+    }
+}
+
+/// A float axis whose grid lines and labels fall only on the given ticks,
+/// rather than on round numbers that mean nothing once hours are compressed.
+struct TickAxis {
+    range: RangedCoordf64,
+    ticks: Vec<f64>,
+}
+
+impl TickAxis {
+    fn new(range: Range<f64>, ticks: &[(f64, String)]) -> TickAxis {
+        TickAxis {
+            range: range.into(),
+            ticks: ticks.iter().map(|t| t.0).collect(),
+        }
+    }
+}
+
+impl Ranged for TickAxis {
+    type FormatOption = NoDefaultFormatting;
+    type ValueType = f64;
+
+    fn map(&self, value: &f64, limit: (i32, i32)) -> i32 {
+        self.range.map(value, limit)
+    }
+
+    fn key_points<Hint: KeyPointHint>(&self, hint: Hint) -> Vec<f64> {
+        if hint.weight().allow_light_points() {
+            vec![]
+        } else {
+            self.ticks.clone()
+        }
+    }
+
+    fn range(&self) -> Range<f64> {
+        self.range.range()
+    }
+}
+
+impl ValueFormatter<f64> for TickAxis {
+    fn format(value: &f64) -> String {
+        RangedCoordf64::format(value)
+    }
+}
+
+/// The price pane's y axis. Values stay in price terms whatever the scale, so
+/// bars and overlays draw the same way on all of them.
+#[derive(Clone)]
+struct PriceAxis {
+    scale: PriceScale,
+    range: RangedCoordf64,
+    /// The range in natural logs, used to place values on a log scale.
+    log_range: RangedCoordf64,
+    /// The first close, which percent labels are measured from.
+    base: f64,
+}
+
+impl PriceAxis {
+    /// Falls back to a linear scale for ranges a log scale can't show.
+    fn new(scale: PriceScale, range: Range<f64>, base: f64) -> PriceAxis {
+        let scale = match scale {
+            PriceScale::Log if range.start <= 0.0 => PriceScale::Linear,
+            PriceScale::Percent if base <= 0.0 => PriceScale::Linear,
+            scale => scale,
+        };
+        let log_range = match scale {
+            PriceScale::Log => range.start.ln()..range.end.ln(),
+            _ => 0.0..1.0,
+        };
+
+        PriceAxis {
+            scale,
+            range: range.into(),
+            log_range: log_range.into(),
+            base,
+        }
+    }
+
+    fn percent(&self, price: f64) -> f64 {
+        (price / self.base - 1.0) * 100.0
+    }
+
+    fn label(&self, price: &f64) -> String {
+        match self.scale {
+            PriceScale::Percent => {
+                let range = self.range.range();
+                let decimals = if self.percent(range.end) - self.percent(range.start) < 10.0 { 1 } else { 0 };
+                let factor = 10f64.powi(decimals as i32);
+                // Adding zero turns a rounded -0 into 0.
+                let percent = (self.percent(*price) * factor).round() / factor + 0.0;
+                if percent == 0.0 {
+                    "0%".to_string()
+                } else {
+                    format!("{:+.*}%", decimals, percent)
+                }
+            }
+            _ => format!("{:.2}", price),
+        }
+    }
+}
+
+impl Ranged for PriceAxis {
+    type FormatOption = NoDefaultFormatting;
+    type ValueType = f64;
+
+    fn map(&self, value: &f64, limit: (i32, i32)) -> i32 {
+        match self.scale {
+            PriceScale::Log => self.log_range.map(&value.max(f64::MIN_POSITIVE).ln(), limit),
+            _ => self.range.map(value, limit),
+        }
+    }
+
+    /// Log ticks land on round prices and percent ticks on round changes, each
+    /// placed back in price terms.
+    fn key_points<Hint: KeyPointHint>(&self, hint: Hint) -> Vec<f64> {
+        if self.scale == PriceScale::Linear {
+            return self.range.key_points(hint);
+        }
+        if hint.weight().allow_light_points() {
+            return vec![];
+        }
+
+        let range = self.range.range();
+        match self.scale {
+            PriceScale::Log => {
+                let ticks = log_ticks(&range, hint.max_num_points());
+                // Over a narrow range a log scale is nearly linear, and so are its ticks.
+                if ticks.len() < 3 {
+                    self.range.key_points(hint)
+                } else {
+                    ticks
+                }
+            }
+            _ => RangedCoordf64::from(self.percent(range.start)..self.percent(range.end))
+                .key_points(hint)
+                .into_iter()
+                .map(|percent| self.base * (1.0 + percent / 100.0))
+                .collect(),
+        }
+    }
+
+    fn range(&self) -> Range<f64> {
+        self.range.range()
+    }
+}
+
+impl ValueFormatter<f64> for PriceAxis {
+    fn format(value: &f64) -> String {
+        RangedCoordf64::format(value)
+    }
+}
+
+/// Round prices for a log axis: as many of each power of ten's multiples as
+/// fit, thinning to every few powers of ten over very wide ranges.
+fn log_ticks(range: &Range<f64>, max_points: usize) -> Vec<f64> {
+    let first = range.start.log10().floor() as i32;
+    let last = range.end.log10().ceil() as i32;
+    let multiples = |steps: &'static [f64]| {
+        (first..=last)
+            .flat_map(move |e| steps.iter().map(move |m| m * 10f64.powi(e)))
+            .filter(|v| range.contains(v))
+            .collect::<Vec<f64>>()
+    };
+
+    for steps in [&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0][..], &[1.0, 2.0, 5.0], &[1.0, 3.0]] {
+        let ticks = multiples(steps);
+        if ticks.len() <= max_points {
+            return ticks;
+        }
+    }
+
+    let powers = multiples(&[1.0]);
+    let step = powers.len().div_ceil(max_points.max(1));
+    powers.into_iter().step_by(step).collect()
+}
+
+/// Where each bar sits along the x axis.
+fn x_positions(dates: &[i64], time_axis: TimeAxis) -> Vec<f64> {
+    match time_axis {
+        TimeAxis::Continuous => dates.iter().map(|d| *d as f64).collect(),
+        TimeAxis::Gapless => (0..dates.len()).map(|i| i as f64).collect(),
+    }
+}
+
+const MAX_X_TICKS: usize = 6;
+
+/// Labelled ticks at the start of each trading day, thinned out to fit. A
+/// single day is split evenly instead.
+fn x_ticks(dates: &[i64], xs: &[f64], time_frame: TimeFrame) -> Vec<(f64, String)> {
+    let market_hours = MarketHours::default();
+    let mut starts = (0..dates.len())
+        .filter(|i| *i == 0 || market_hours.trading_day(dates[*i]) != market_hours.trading_day(dates[i - 1]))
+        .collect::<Vec<usize>>();
+
+    if starts.len() < 2 {
+        starts = (0..MAX_X_TICKS - 1)
+            .map(|i| i * (dates.len() - 1) / (MAX_X_TICKS - 2))
+            .collect();
+        starts.dedup();
+    }
+
+    let step = starts.len().div_ceil(MAX_X_TICKS);
+    starts
+        .into_iter()
+        .step_by(step)
+        .map(|i| (xs[i], time_frame.format_time(dates[i])))
+        .collect()
+}
+
+/// Index ranges of each run of consecutive bars in pre or post market.
+fn extended_hours(bars: &[Price], periods: &ChartCurrentTradingPeriod) -> Vec<(usize, usize)> {
+    let mut spans: Vec<(usize, usize)> = vec![];
+    let mut in_span = false;
+
+    for (i, bar) in bars.iter().enumerate() {
+        let extended = TradingPeriod::of(bar.date, periods).is_some_and(TradingPeriod::is_extended);
+        match spans.last_mut() {
+            Some(span) if extended && in_span => span.1 = i,
+            _ if extended => spans.push((i, i)),
+            _ => {}
+        }
+        in_span = extended;
+    }
+
+    spans
+}
+
+/// Splits `range` into `count` evenly spaced dashes with gaps between them.
+fn dashes(range: Range<f64>, count: usize) -> impl Iterator<Item = (f64, f64)> {
+    let step = (range.end - range.start) / (count * 2) as f64;
+    (0..count).map(move |i| {
+        let start = range.start + (i * 2) as f64 * step;
+        (start, start + step)
+    })
+}
+
+/// Draws onto an in-memory bitmap of `size` and returns it encoded as a PNG.
+pub fn render_png<F>(size: (u32, u32), draw: F) -> Result<Vec<u8>, Box<dyn std::error::Error>>
+where
+    F: FnOnce(&DrawingArea<BitMapBackend, Shift>) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let mut buffer = vec![0; size.0 as usize * size.1 as usize * 3];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, size).into_drawing_area();
+        draw(&root)?;
+        root.present()?;
+    }
+
+    let mut png = vec![];
+    PngEncoder::new(&mut png).encode(&buffer, size.0, size.1, ColorType::Rgb8)?;
+    Ok(png)
+}
+
+/// Draws `frames` frames into an animated GIF. The backend only writes to
+/// files, so this goes through a temporary one.
+pub fn render_gif<F>(
+    size: (u32, u32),
+    frame_delay_ms: u32,
+    frames: usize,
+    draw: F,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>>
+where
+    F: FnMut(&DrawingArea<BitMapBackend, Shift>, usize) -> Result<(), Box<dyn std::error::Error>>,
+{
+    static NEXT_GIF: AtomicUsize = AtomicUsize::new(0);
+    let path = env::temp_dir().join(format!(
+        "stout-{}-{}.gif",
+        process::id(),
+        NEXT_GIF.fetch_add(1, Ordering::SeqCst)
+    ));
+
+    let gif = write_gif(&path, size, frame_delay_ms, frames, draw).and_then(|_| Ok(fs::read(&path)?));
+    let _ = fs::remove_file(&path);
+    gif
+}
+
+fn write_gif<F>(
+    path: &Path,
+    size: (u32, u32),
+    frame_delay_ms: u32,
+    frames: usize,
+    mut draw: F,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnMut(&DrawingArea<BitMapBackend, Shift>, usize) -> Result<(), Box<dyn std::error::Error>>,
+{
+    // The file is only complete once the backend is dropped.
+    let root = BitMapBackend::gif(path, size, frame_delay_ms)?.into_drawing_area();
+    for frame in 0..frames {
+        draw(&root, frame)?;
+        root.present()?;
+    }
+    Ok(())
+}
+
+/// Draws onto an in-memory SVG document of `size` and returns its source.
+pub fn render_svg<F>(size: (u32, u32), draw: F) -> Result<String, Box<dyn std::error::Error>>
+where
+    F: FnOnce(&DrawingArea<SVGBackend, Shift>) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, size).into_drawing_area();
+        draw(&root)?;
+        root.present()?;
+    }
+
+    Ok(svg)
+}
+
+/// Pads a value range by 5% on each side so series don't touch the plot edges.
+fn padded_range(values: impl Iterator<Item = f64>) -> Option<Range<f64>> {
+    let (min, max) = values.fold(None, |acc: Option<(f64, f64)>, v| match acc {
+        Some((min, max)) => Some((min.min(v), max.max(v))),
+        None => Some((v, v)),
+    })?;
+
+    let padding = if max > min { (max - min) * 0.05 } else { max.abs() * 0.05 + 0.01 };
+    Some(min - padding..max + padding)
+}
+
+/// Like `padded_range`, but padding a log scale by ratio so it stays positive.
+fn price_range(values: impl Iterator<Item = f64>, price_scale: PriceScale) -> Option<Range<f64>> {
+    let values = values.collect::<Vec<f64>>();
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+
+    if price_scale == PriceScale::Log && min > 0.0 && max > min {
+        let padding = (max / min).powf(0.05);
+        return Some(min / padding..max * padding);
+    }
+    padded_range(values.into_iter())
+}
+
+/// Draws each symbol's percent change on one chart, with its final return
+/// labelled at the right edge.
+pub fn build_comparison_chart(comparison: &Comparison, theme: Theme) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let palette = theme.palette();
+    let scale = TimeScale::new(
+        &comparison.dates,
+        TimeAxis::default_for(comparison.time_frame),
+        comparison.time_frame,
+    );
+    let values = comparison.series.iter().flat_map(|(_, values)| values.iter().copied());
+    let y_range = padded_range(values.chain(std::iter::once(0.0))).ok_or("No price data to compare")?;
+
+    render_png(CHART_SIZE, |root| {
+        root.fill(&palette.background)?;
+        let mut chart = ChartBuilder::on(root)
+            .margin(10)
+            .margin_right(60)
+            .x_label_area_size(X_LABEL_AREA_SIZE)
+            .y_label_area_size(50)
+            .build_cartesian_2d(scale.axis(), y_range)?;
+
+        chart
+            .configure_mesh()
+            .label_style(palette.font(12))
+            .y_labels(6)
+            .x_label_formatter(&|x| scale.label(x))
+            .y_label_formatter(&|v| format!("{:+.1}%", v * 100.0))
+            .axis_style(&palette.foreground)
+            .bold_line_style(&palette.grid)
+            .light_line_style(&palette.background)
+            .draw()?;
+
+        chart.draw_series(dashes(scale.range(), 40).map(|(start, end)| {
+            PathElement::new(vec![(start, 0.0), (end, 0.0)], &palette.foreground.mix(0.4))
+        }))?;
+
+        let mut annotations = vec![];
+        for (n, (symbol, values)) in comparison.series.iter().enumerate() {
+            let color = palette.series(n);
+            let last = values[values.len() - 1];
+
+            chart
+                .draw_series(LineSeries::new(
+                    scale.xs.iter().copied().zip(values.iter().copied()),
+                    color.stroke_width(2),
+                ))?
+                .label(symbol.as_str())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 15, y)], color.stroke_width(2)));
+
+            let (x, y) = chart.backend_coord(&(scale.xs[scale.xs.len() - 1], last));
+            annotations.push((x + 5, y, format!("{:+.1}%", last * 100.0), color));
+        }
+
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .label_font(palette.font(12))
+            .background_style(&palette.background.mix(0.8))
+            .border_style(&palette.foreground.mix(0.3))
+            .draw()?;
+
+        // Push apart labels for symbols that finished close together.
+        annotations.sort_by_key(|a| a.1);
+        for i in 1..annotations.len() {
+            annotations[i].1 = annotations[i].1.max(annotations[i - 1].1 + 14);
+        }
+        for (x, y, label, color) in annotations {
+            root.draw(&Text::new(label, (x, y - 6), palette.font(12).color(color)))?;
+        }
+        Ok(())
+    })
+}
+
+/// Draws one compact cell per stock, with its symbol, price and change beside
+/// a sparkline of its closes, so a watchlist fits in a single image.
+pub fn build_sparkline_grid(stocks: &[Stock], theme: Theme) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if stocks.is_empty() {
+        return Err("No stocks to chart".into());
+    }
+
+    let palette = theme.palette();
+    let columns = SPARKLINE_COLUMNS.min(stocks.len());
+    let rows = stocks.len().div_ceil(columns);
+    let size = (
+        SPARKLINE_CELL_SIZE.0 * columns as u32,
+        SPARKLINE_CELL_SIZE.1 * rows as u32,
+    );
+
+    render_png(size, |root| {
+        root.fill(&palette.background)?;
+        for (cell, stock) in root.split_evenly((rows, columns)).iter().zip(stocks) {
+            draw_sparkline_cell(cell, stock, palette)?;
+        }
+        Ok(())
+    })
+}
+
+fn draw_sparkline_cell<DB: DrawingBackend>(
+    cell: &DrawingArea<DB, Shift>,
+    stock: &Stock,
+    palette: &'static Palette,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let color = palette.change(stock.pct_change);
+    let (width, height) = cell.dim_in_pixel();
+    cell.draw(&Rectangle::new(
+        [(0, 0), (width as i32 - 1, height as i32 - 1)],
+        &palette.grid,
+    ))?;
+
+    let title = match stock.time_frame {
+        TimeFrame::Day1 => stock.symbol.clone(),
+        time_frame => format!("{} {}", stock.symbol, time_frame.as_str()),
+    };
+    let title_style = ("sans-serif", 16)
+        .into_font()
+        .style(FontStyle::Bold)
+        .color(&palette.foreground);
+    let right = SPARKLINE_LABEL_WIDTH as i32 - 8;
+    cell.draw(&Text::new(title, (10, 10), title_style))?;
+    let price = symbols::format_price(stock.current_price, stock.instrument, stock.currency.as_deref());
+    cell.draw(&Text::new(price, (10, 34), palette.font(13)))?;
+    cell.draw(&Text::new(
+        format!("{:+.2}%", stock.pct_change * 100.0),
+        (right, 34),
+        palette.font(13).color(color).pos(Pos::new(HPos::Right, VPos::Top)),
+    ))?;
+
+    let closes = stock.prices.iter().map(|p| p.close).filter(|c| *c > 0.0).collect::<Vec<f64>>();
+    if closes.len() < 2 {
+        return Ok(());
+    }
+
+    let x_range = 0.0..(closes.len() - 1) as f64;
+    let y_range = padded_range(closes.iter().copied().chain(stock.previous_close)).unwrap();
+    let (_, spark) = cell.split_horizontally(SPARKLINE_LABEL_WIDTH);
+    let mut chart = ChartBuilder::on(&spark)
+        .margin(8)
+        .build_cartesian_2d(x_range.clone(), y_range.clone())?;
+
+    if let Some(previous_close) = stock.previous_close {
+        chart.draw_series(dashes(x_range, 20).map(|(start, end)| {
+            PathElement::new(vec![(start, previous_close), (end, previous_close)], &palette.foreground.mix(0.4))
+        }))?;
+    }
+
+    let points = closes.iter().enumerate().map(|(i, close)| (i as f64, *close));
+    chart.draw_series(AreaSeries::new(points.clone(), y_range.start, &color.mix(0.15)))?;
+    chart.draw_series(LineSeries::new(points, color.stroke_width(2)))?;
+
+    Ok(())
+}
+
+/// Draws the tiles as a treemap grouped by sector, sized by market cap and
+/// shaded by change, saturating at `HEATMAP_FULL_CHANGE`.
+pub fn build_heatmap(tiles: &[Tile], theme: Theme) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let palette = theme.palette();
+    let bounds = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: HEATMAP_SIZE.0 as f64,
+        height: HEATMAP_SIZE.1 as f64,
+    };
+    let sectors = heatmap::layout(tiles, bounds, HEATMAP_HEADER_HEIGHT);
+    let corners = |r: &Rect| {
+        [
+            (r.x.round() as i32, r.y.round() as i32),
+            ((r.x + r.width).round() as i32 - 1, (r.y + r.height).round() as i32 - 1),
+        ]
+    };
+
+    render_png(HEATMAP_SIZE, |root| {
+        root.fill(&palette.background)?;
+
+        for sector in &sectors {
+            for (tile, rect) in &sector.tiles {
+                root.draw(&Rectangle::new(corners(rect), heat_color(palette, tile.change).filled()))?;
+                root.draw(&Rectangle::new(corners(rect), &palette.background))?;
+
+                let size = (rect.width / 5.0).min(rect.height / 3.0).min(22.0) as u32;
+                if size < 11 {
+                    continue;
+                }
+                let (x, y) = ((rect.x + rect.width / 2.0) as i32, (rect.y + rect.height / 2.0) as i32);
+                let symbol_style = ("sans-serif", size)
+                    .into_font()
+                    .style(FontStyle::Bold)
+                    .color(&palette.foreground)
+                    .pos(Pos::new(HPos::Center, VPos::Bottom));
+                let change_style = palette.font(size * 3 / 4).pos(Pos::new(HPos::Center, VPos::Top));
+                root.draw(&Text::new(tile.symbol.clone(), (x, y - 1), symbol_style))?;
+                root.draw(&Text::new(format!("{:+.2}%", tile.change * 100.0), (x, y + 2), change_style))?;
+            }
+
+            if let Some(header) = &sector.header {
+                root.draw(&Rectangle::new(corners(header), palette.grid.filled()))?;
+                root.draw(&Text::new(
+                    sector.name.to_uppercase(),
+                    (header.x as i32 + 4, header.y as i32 + 2),
+                    palette.font(11),
+                ))?;
+            }
+            root.draw(&Rectangle::new(corners(&sector.bounds), palette.background.stroke_width(2)))?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Blends from the neutral grid color toward the up or down color as the
+/// change grows.
+fn heat_color(palette: &Palette, change: f64) -> RGBColor {
+    let target = palette.change(change);
+    let t = (change.abs() / HEATMAP_FULL_CHANGE).min(1.0);
+    let blend = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * t).round() as u8;
+
+    RGBColor(
+        blend(palette.grid.0, target.0),
+        blend(palette.grid.1, target.1),
+        blend(palette.grid.2, target.2),
+    )
+}
+
+/// Plots implied volatility by strike for calls and puts of one expiration,
+/// with a marker at the underlying price.
+pub fn build_iv_smile_chart(
+    symbol: &str,
+    expiration_date: i64,
+    underlying_price: f64,
+    calls: &[(f64, f64)],
+    puts: &[(f64, f64)],
+    theme: Theme,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let palette = theme.palette();
+    let points = || calls.iter().chain(puts.iter());
+    let x_range = padded_range(points().map(|p| p.0).chain(std::iter::once(underlying_price)))
+        .ok_or("No implied volatility data")?;
+    let y_range = padded_range(points().map(|p| p.1)).ok_or("No implied volatility data")?;
+
+    render_png(OPTIONS_CHART_SIZE, |root| {
+        root.fill(&palette.background)?;
+        let mut chart = ChartBuilder::on(root)
+            .caption(
+                format!("{} IV smile {}", symbol, options::format_expiration(expiration_date)),
+                palette.font(20),
+            )
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(50)
+            .build_cartesian_2d(x_range, y_range.clone())?;
+
+        chart
+            .configure_mesh()
+            .x_desc("Strike")
+            .y_label_formatter(&|v| format!("{:.1}%", v * 100.0))
+            .label_style(palette.font(12))
+            .axis_style(&palette.foreground)
+            .bold_line_style(&palette.grid)
+            .light_line_style(&palette.grid.mix(0.3))
+            .draw()?;
+
+        chart.draw_series(LineSeries::new(
+            vec![(underlying_price, y_range.start), (underlying_price, y_range.end)],
+            &palette.foreground.mix(0.3),
+        ))?;
+
+        for (label, series, color) in [("Calls", calls, &palette.up), ("Puts", puts, &palette.down)] {
+            chart
+                .draw_series(LineSeries::new(series.iter().copied(), color))?
+                .label(label)
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+
+        chart
+            .configure_series_labels()
+            .label_font(palette.font(12))
+            .background_style(&palette.background.mix(0.8))
+            .border_style(&palette.foreground)
+            .draw()?;
+        Ok(())
+    })
+}
+
+/// Plots at-the-money implied volatility against expiration date.
+pub fn build_iv_term_structure_chart(
+    symbol: &str,
+    points: &[(i64, f64)],
+    theme: Theme,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let palette = theme.palette();
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first.0, last.0),
+        _ => return Err("No implied volatility data".into()),
+    };
+    // Leave room either side of a lone expiration.
+    let x_range = if first == last { first - 86400..last + 86400 } else { first..last };
+    let y_range = padded_range(points.iter().map(|p| p.1)).ok_or("No implied volatility data")?;
+
+    render_png(OPTIONS_CHART_SIZE, |root| {
+        root.fill(&palette.background)?;
+        let mut chart = ChartBuilder::on(root)
+            .caption(format!("{} ATM IV term structure", symbol), palette.font(20))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(50)
+            .build_cartesian_2d(x_range, y_range)?;
+
+        chart
+            .configure_mesh()
+            .x_labels(6)
+            .x_label_formatter(&|t| options::format_expiration(*t))
+            .y_label_formatter(&|v| format!("{:.1}%", v * 100.0))
+            .label_style(palette.font(12))
+            .axis_style(&palette.foreground)
+            .bold_line_style(&palette.grid)
+            .light_line_style(&palette.grid.mix(0.3))
+            .draw()?;
+
+        let color = palette.series(0);
+        chart.draw_series(LineSeries::new(points.iter().copied(), color))?;
+        chart.draw_series(points.iter().map(|p| Circle::new(*p, 3, color.filled())))?;
+        Ok(())
+    })
+}
+
+/// Plots profit and loss at expiry across underlying prices, marking the
+/// current price and the breakevens.
+pub fn build_payoff_chart(
+    symbol: &str,
+    strategy: &Strategy,
+    underlying_price: f64,
+    theme: Theme,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let palette = theme.palette();
+    let strikes = strategy.strikes();
+    let breakevens = strategy.breakevens();
+    let x_range = padded_range(
+        strikes
+            .iter()
+            .chain(breakevens.iter())
+            .copied()
+            .chain(std::iter::once(underlying_price)),
+    )
+    .ok_or("No legs to plot")?;
+    // Widen the view so the slope past the outermost strikes is visible.
+    let span = (x_range.end - x_range.start).max(underlying_price * 0.1);
+    let x_range = (x_range.start - span * 0.25).max(0.0)..x_range.end + span * 0.25;
+
+    // The payoff is linear between strikes, so these points draw it exactly
+    // and let the profit and loss areas be split at zero without artifacts.
+    let mut xs = vec![x_range.start, x_range.end];
+    xs.extend(strikes.iter().chain(breakevens.iter()).filter(|x| x_range.contains(x)));
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let points = xs.iter().map(|x| (*x, strategy.payoff(*x))).collect::<Vec<(f64, f64)>>();
+
+    let y_range = padded_range(points.iter().map(|p| p.1).chain(std::iter::once(0.0)))
+        .ok_or("No legs to plot")?;
+
+    render_png(OPTIONS_CHART_SIZE, |root| {
+        root.fill(&palette.background)?;
+        let mut chart = ChartBuilder::on(root)
+            .caption(
+                format!("{} payoff at {}", symbol, options::format_expiration(strategy.expiration_date)),
+                palette.font(20),
+            )
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(60)
+            .build_cartesian_2d(x_range.clone(), y_range.clone())?;
+
+        chart
+            .configure_mesh()
+            .x_desc("Price at expiry")
+            .y_label_formatter(&|v| format!("${:.0}", v))
+            .label_style(palette.font(12))
+            .axis_style(&palette.foreground)
+            .bold_line_style(&palette.grid)
+            .light_line_style(&palette.grid.mix(0.3))
+            .draw()?;
+
+        chart.draw_series(AreaSeries::new(
+            points.iter().map(|(x, y)| (*x, y.max(0.0))),
+            0.0,
+            &palette.up.mix(0.2),
+        ))?;
+        chart.draw_series(AreaSeries::new(
+            points.iter().map(|(x, y)| (*x, y.min(0.0))),
+            0.0,
+            &palette.down.mix(0.2),
+        ))?;
+        chart.draw_series(LineSeries::new(
+            vec![(x_range.start, 0.0), (x_range.end, 0.0)],
+            &palette.foreground.mix(0.5),
+        ))?;
+        chart.draw_series(LineSeries::new(
+            vec![(underlying_price, y_range.start), (underlying_price, y_range.end)],
+            &palette.series(0).mix(0.5),
+        ))?;
+        chart.draw_series(LineSeries::new(points.iter().copied(), &palette.foreground))?;
+        chart.draw_series(breakevens.iter().map(|x| {
+            EmptyElement::at((*x, 0.0))
+                + Circle::new((0, 0), 3, palette.foreground.filled())
+                + Text::new(format!("{:.2}", x), (5, 5), palette.font(12))
+        }))?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::model::{ChartTradingPeriod, OptionsHeader};
+    use crate::common::TimeFrame;
+    use crate::symbols::Instrument;
+    use plotters::coord::ranged1d::BoldPoints;
+
+    const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+    fn stock(closes: &[f64]) -> Stock {
+        let prices = closes
+            .iter()
+            .enumerate()
+            .map(|(i, close)| Price {
+                close: *close,
+                volume: 1000 + i as u64 * 100,
+                high: close + 0.5,
+                low: close - 0.5,
+                open: close - 0.25,
+                date: 1613745000 + i as i64 * 60,
+            })
+            .collect();
+
+        Stock {
+            high: 0.0,
+            low: 0.0,
+            symbol: "TEST".to_string(),
+            prices,
+            current_price: *closes.last().unwrap_or(&0.0),
+            time_frame: TimeFrame::Day1,
+            pct_change: 0.01,
+            company: None,
+            market_cap: None,
+            instrument: Instrument::Equity,
+            currency: None,
+            contract: None,
+            previous_close: None,
+            trading_periods: None,
+            events: vec![],
+        }
+    }
+
+    fn trading_periods() -> ChartCurrentTradingPeriod {
+        let period = |start, end| ChartTradingPeriod { start, end };
+
+        ChartCurrentTradingPeriod {
+            pre: period(1613745000, 1613745120),
+            regular: period(1613745120, 1613745300),
+            post: period(1613745300, 1613745480),
+        }
+    }
+
+    #[test]
+    fn it_renders_price_charts_in_memory() {
+        let stock = stock(&[10.0, 0.0, 10.5, 10.25, 11.0]);
+
+        for chart_type in [ChartType::Line, ChartType::Candle, ChartType::Ohlc] {
+            for time_axis in [TimeAxis::Continuous, TimeAxis::Gapless] {
+                for price_scale in [PriceScale::Linear, PriceScale::Log, PriceScale::Percent] {
+                    let options = ChartOptions {
+                        chart_type,
+                        format: ChartFormat::Png,
+                        time_axis,
+                        price_scale,
+                        indicators: vec![],
+                        theme: Theme::default(),
+                    };
+                    let png = build_chart(&stock, options.clone()).unwrap();
+                    assert_eq!(png[..8], PNG_SIGNATURE);
+
+                    let options = ChartOptions {
+                        format: ChartFormat::Svg,
+                        ..options
+                    };
+                    let svg = String::from_utf8(build_chart(&stock, options).unwrap()).unwrap();
+                    assert!(svg.starts_with("<svg"));
+                }
+            }
+        }
+
+        let options = ChartOptions::for_time_frame(TimeFrame::Day1);
+        assert!(build_chart(&self::stock(&[10.0, 0.0]), options).is_err());
+    }
+
+    #[test]
+    fn it_renders_in_every_theme() {
+        let mut stock = stock(&[10.0, 10.5, 10.25, 11.0, 10.75, 10.8, 10.9]);
+        stock.previous_close = Some(10.4);
+        stock.trading_periods = Some(trading_periods());
+
+        for theme in Theme::ALL.iter().copied() {
+            let options = ChartOptions {
+                chart_type: ChartType::Candle,
+                theme,
+                ..ChartOptions::for_time_frame(TimeFrame::Day1)
+            };
+            let png = build_chart(&stock, options).unwrap();
+            assert_eq!(png[..8], PNG_SIGNATURE);
+        }
+
+        // The background follows the theme.
+        let options = ChartOptions {
+            format: ChartFormat::Svg,
+            theme: Theme::Dark,
+            ..ChartOptions::for_time_frame(TimeFrame::Day1)
+        };
+        let svg = String::from_utf8(build_chart(&stock, options).unwrap()).unwrap();
+        assert!(svg.contains("#36393F"));
+    }
+
+    #[test]
+    fn it_renders_intraday_sessions() {
+        let mut stock = stock(&[10.0, 10.5, 10.25, 11.0, 10.75, 10.8, 10.9]);
+        stock.previous_close = Some(9.5);
+        stock.trading_periods = Some(trading_periods());
+
+        let options = ChartOptions {
+            chart_type: ChartType::Candle,
+            ..ChartOptions::for_time_frame(TimeFrame::Day1)
+        };
+        let png = build_chart(&stock, options).unwrap();
+        assert_eq!(png[..8], PNG_SIGNATURE);
+    }
+
+    #[test]
+    fn it_finds_extended_hours_spans() {
+        // One bar a minute: two pre market, three regular, two post market.
+        let stock = stock(&[10.0, 10.5, 10.25, 11.0, 10.75, 10.8, 10.9]);
+        assert_eq!(
+            extended_hours(&stock.prices, &trading_periods()),
+            [(0, 1), (5, 6)]
+        );
+
+        assert_eq!(
+            dashes(0.0..100.0, 5).collect::<Vec<(f64, f64)>>(),
+            [(0.0, 10.0), (20.0, 30.0), (40.0, 50.0), (60.0, 70.0), (80.0, 90.0)]
+        );
+    }
+
+    #[test]
+    fn it_compresses_closed_hours_on_a_gapless_axis() {
+        // Two sessions of three hourly bars, a day apart.
+        let dates = [1613743200, 1613746800, 1613750400, 1613829600, 1613833200, 1613836800];
+
+        let xs = x_positions(&dates, TimeAxis::Gapless);
+        assert_eq!(xs, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(x_positions(&dates, TimeAxis::Continuous)[3], 1613829600.0);
+
+        let ticks = x_ticks(&dates, &xs, TimeFrame::Week1);
+        assert_eq!(ticks.iter().map(|t| t.0).collect::<Vec<f64>>(), [0.0, 3.0]);
+
+        let ticks = x_ticks(&dates[..3], &xs[..3], TimeFrame::Day1);
+        assert_eq!(ticks.iter().map(|t| t.0).collect::<Vec<f64>>(), [0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn it_parses_chart_types() {
+        assert_eq!("candle".parse(), Ok(ChartType::Candle));
+        assert_eq!("OHLC".parse(), Ok(ChartType::Ohlc));
+        assert_eq!("line".parse(), Ok(ChartType::Line));
+        assert!("bar".parse::<ChartType>().is_err());
+    }
+
+    #[test]
+    fn it_parses_indicators() {
+        assert_eq!("sma50".parse(), Ok(Indicator::Sma(50)));
+        assert_eq!("EMA".parse(), Ok(Indicator::Ema(20)));
+        assert_eq!("bb".parse(), Ok(Indicator::Bollinger(20)));
+        assert_eq!("rsi7".parse(), Ok(Indicator::Rsi(7)));
+        assert_eq!("macd".parse(), Ok(Indicator::Macd));
+        assert_eq!("atr".parse(), Ok(Indicator::Atr(14)));
+        assert_eq!("stoch5".parse(), Ok(Indicator::Stochastic(5)));
+        assert_eq!("OBV".parse(), Ok(Indicator::Obv));
+        assert!("macd5".parse::<Indicator>().is_err());
+        assert!("sma1".parse::<Indicator>().is_err());
+        assert!("3M".parse::<Indicator>().is_err());
+    }
+
+    #[test]
+    fn it_parses_price_scales() {
+        assert_eq!("log".parse(), Ok(PriceScale::Log));
+        assert_eq!("PCT".parse(), Ok(PriceScale::Percent));
+        assert_eq!("%".parse(), Ok(PriceScale::Percent));
+        assert_eq!("linear".parse(), Ok(PriceScale::Linear));
+        assert!("ln".parse::<PriceScale>().is_err());
+        assert_eq!(PriceScale::default_for(TimeFrame::Year5), PriceScale::Log);
+        assert_eq!(PriceScale::default_for(TimeFrame::Year1), PriceScale::Linear);
+    }
+
+    #[test]
+    fn it_places_log_ticks_on_round_prices() {
+        assert_eq!(log_ticks(&(15.0..95.0), 10), [20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0]);
+        assert_eq!(log_ticks(&(15.0..450.0), 6), [20.0, 50.0, 100.0, 200.0]);
+        assert_eq!(log_ticks(&(0.5..5000.0), 6), [1.0, 10.0, 100.0, 1000.0]);
+        assert_eq!(log_ticks(&(1.0..2e8), 4), [1.0, 1e3, 1e6]);
+
+        // Equal ratios are equal distances.
+        let axis = PriceAxis::new(PriceScale::Log, 10.0..1000.0, 10.0);
+        assert_eq!(axis.map(&100.0, (0, 200)), 100);
+        assert_eq!(axis.map(&1000.0, (0, 200)), 200);
+        let ticks = axis.key_points(BoldPoints(6));
+        assert!(ticks.contains(&100.0) && ticks.iter().all(|t| (10.0..=1000.0).contains(t)));
+    }
+
+    #[test]
+    fn it_labels_percent_ticks_from_the_first_close() {
+        let axis = PriceAxis::new(PriceScale::Percent, 40.0..130.0, 50.0);
+        let labels = axis.key_points(BoldPoints(6)).iter().map(|t| axis.label(t)).collect::<Vec<String>>();
+        assert_eq!(labels, ["0%", "+50%", "+100%", "+150%"]);
+        assert_eq!(axis.label(&50.0), "0%");
+
+        let narrow = PriceAxis::new(PriceScale::Percent, 99.0..102.0, 100.0);
+        assert_eq!(narrow.label(&101.5), "+1.5%");
+        assert_eq!(narrow.label(&99.96), "0%");
+
+        // A log scale needs positive prices.
+        assert!(PriceAxis::new(PriceScale::Log, -1.0..1.0, 1.0).scale == PriceScale::Linear);
+    }
+
+    #[test]
+    fn it_replays_bars_in_frames() {
+        let frames = replay_frames(600, 60);
+        assert_eq!(frames.len(), 60 + REPLAY_HOLD_FRAMES);
+        assert_eq!(frames[..3], [10, 20, 30]);
+        assert!(frames[59..].iter().all(|n| *n == 600));
+
+        // Fewer bars than frames shows one more bar each frame.
+        let frames = replay_frames(3, 60);
+        assert_eq!(frames[..4], [1, 2, 3, 3]);
+    }
+
+    #[test]
+    fn it_renders_replays_as_gifs() {
+        let mut stock = stock(&[10.0, 10.5, 10.25, 11.0, 10.75, 10.8, 10.9]);
+        stock.previous_close = Some(10.4);
+        stock.trading_periods = Some(trading_periods());
+
+        let options = ChartOptions {
+            chart_type: ChartType::Candle,
+            indicators: vec![Indicator::Sma(2), Indicator::Rsi(2)],
+            ..ChartOptions::for_time_frame(TimeFrame::Day1)
+        };
+        let gif = build_replay(&stock, options).unwrap();
+        assert_eq!(gif[..6], *b"GIF89a");
+    }
+
+    #[test]
+    fn it_renders_sparkline_grids() {
+        let mut stocks = (0..3)
+            .map(|n| stock(&[10.0, 10.5 + n as f64, 0.0, 10.25, 11.0]))
+            .collect::<Vec<Stock>>();
+        stocks[1].pct_change = -0.02;
+        stocks[2].prices.truncate(1);
+
+        let png = build_sparkline_grid(&stocks, Theme::Dark).unwrap();
+        assert_eq!(png[..8], PNG_SIGNATURE);
+        let width = u32::from_be_bytes([png[16], png[17], png[18], png[19]]);
+        let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+        assert_eq!((width, height), (2 * SPARKLINE_CELL_SIZE.0, 2 * SPARKLINE_CELL_SIZE.1));
+
+        assert!(build_sparkline_grid(&[], Theme::Light).is_err());
+    }
+
+    #[test]
+    fn it_renders_heatmaps() {
+        let tile = |symbol: &str, sector: &str, market_cap, change| Tile {
+            symbol: symbol.to_string(),
+            sector: sector.to_string(),
+            market_cap,
+            change,
+        };
+        let tiles = [
+            tile("AAPL", "Technology", 2000.0, 0.012),
+            tile("MSFT", "Technology", 1800.0, -0.004),
+            tile("XOM", "Energy", 250.0, -0.05),
+            tile("TINY", "Energy", 0.5, 0.0),
+        ];
+
+        let png = build_heatmap(&tiles, Theme::Light).unwrap();
+        assert_eq!(png[..8], PNG_SIGNATURE);
+    }
+
+    #[test]
+    fn it_shades_heatmap_tiles_by_change() {
+        let palette = Theme::Light.palette();
+        let rgb = |c: &RGBColor| (c.0, c.1, c.2);
+        assert_eq!(rgb(&heat_color(palette, 0.0)), rgb(&palette.grid));
+        assert_eq!(rgb(&heat_color(palette, 0.1)), rgb(&palette.up));
+        assert_eq!(rgb(&heat_color(palette, -HEATMAP_FULL_CHANGE)), rgb(&palette.down));
+        assert_eq!(rgb(&heat_color(palette, HEATMAP_FULL_CHANGE / 2.0)), (110, 190, 155));
+    }
+
+    #[test]
+    fn it_places_events_on_their_bars() {
+        let dates = [100, 200, 300, 400];
+        let event = |date, kind| Event { date, kind };
+        let events = [
+            event(50, EventKind::Dividend),
+            event(100, EventKind::Earnings),
+            event(250, EventKind::Dividend),
+            event(299, EventKind::Split),
+            event(450, EventKind::Earnings),
+            event(500, EventKind::Earnings),
+        ];
+
+        assert_eq!(
+            event_markers(&events, &dates),
+            [
+                (0, 0, EventKind::Earnings),
+                (1, 0, EventKind::Dividend),
+                (1, 1, EventKind::Split),
+                (3, 0, EventKind::Earnings),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_renders_event_markers() {
+        let mut stock = stock(&[10.0, 10.5, 10.25, 11.0, 10.75]);
+        let date = stock.prices[2].date;
+        stock.events = vec![
+            Event { date, kind: EventKind::Earnings },
+            Event { date, kind: EventKind::Dividend },
+        ];
+
+        let options = ChartOptions {
+            format: ChartFormat::Svg,
+            ..ChartOptions::for_time_frame(TimeFrame::Day1)
+        };
+        let svg = String::from_utf8(build_chart(&stock, options).unwrap()).unwrap();
+        assert!(svg.contains("\nE\n") && svg.contains("\nD\n"));
+        assert!(svg.contains("Earnings") && svg.contains("Dividend") && !svg.contains("Split"));
+    }
+
+    #[test]
+    fn it_splits_series_into_runs() {
+        let xs = [0.0, 1.0, 2.0, 3.0, 4.0];
+        let values = [None, Some(1.0), Some(2.0), None, Some(4.0)];
+        assert_eq!(
+            runs(&xs, &values, |_| false),
+            [vec![(1.0, 1.0), (2.0, 2.0)], vec![(4.0, 4.0)]]
+        );
+        assert_eq!(runs(&xs, &values, |i| i == 2).len(), 3);
+    }
+
+    #[test]
+    fn it_renders_indicator_panes() {
+        let closes = (0..60).map(|i| 100.0 + (i as f64 / 4.0).sin() * 5.0).collect::<Vec<f64>>();
+        let stock = stock(&closes);
+
+        let options = ChartOptions {
+            indicators: vec![
+                Indicator::Sma(10),
+                Indicator::Sma(200),
+                Indicator::Bollinger(20),
+                Indicator::Vwap,
+                Indicator::Rsi(14),
+                Indicator::Macd,
+            ],
+            ..ChartOptions::for_time_frame(TimeFrame::Day1)
+        };
+        let png = build_chart(&stock, options).unwrap();
+        assert_eq!(png[..8], PNG_SIGNATURE);
+
+        // Two panes below the volume grow the image.
+        let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+        assert_eq!(height, CHART_SIZE.1 + 2 * INDICATOR_PANE_HEIGHT);
+
+        let options = ChartOptions {
+            indicators: vec![
+                Indicator::Atr(14),
+                Indicator::Stochastic(14),
+                Indicator::Obv,
+                Indicator::Rsi(14),
+            ],
+            ..ChartOptions::for_time_frame(TimeFrame::Day1)
+        };
+        let png = build_chart(&stock, options).unwrap();
+        let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+        assert_eq!(height, CHART_SIZE.1 + MAX_INDICATOR_PANES as u32 * INDICATOR_PANE_HEIGHT);
+    }
+
+    #[test]
+    fn it_renders_comparison_charts_as_png() {
+        let comparison = Comparison {
+            time_frame: TimeFrame::Week1,
+            dates: vec![1613743200, 1613746800, 1613829600, 1613833200],
+            series: vec![
+                ("AAPL".to_string(), vec![0.0, 0.01, 0.02, 0.03]),
+                ("MSFT".to_string(), vec![0.0, -0.01, 0.02, 0.031]),
+            ],
+        };
+
+        let png = build_comparison_chart(&comparison, Theme::Dark).unwrap();
+        assert_eq!(png[..8], PNG_SIGNATURE);
+    }
+
+    #[test]
+    fn it_renders_iv_charts_as_png() {
+        let header: OptionsHeader = options::fixture(include_str!("../fixtures/options_spy.json"));
+        let data = &header.options[0];
+        let underlying_price = header.quote.regular_market_price;
+
+        let smile = build_iv_smile_chart(
+            "SPY",
+            data.expiration_date,
+            underlying_price,
+            &options::iv_smile(&data.calls),
+            &options::iv_smile(&data.puts),
+            Theme::Dark,
+        )
+        .unwrap();
+        assert_eq!(smile[..8], PNG_SIGNATURE);
+
+        let term = build_iv_term_structure_chart("SPY", &options::term_structure(&[header]), Theme::Colorblind).unwrap();
+        assert_eq!(term[..8], PNG_SIGNATURE);
+
+        assert!(build_iv_term_structure_chart("SPY", &[], Theme::Light).is_err());
+    }
+
+    #[test]
+    fn it_renders_payoff_charts_as_png() {
+        let header: OptionsHeader = options::fixture(include_str!("../fixtures/options_spy.json"));
+        let legs = crate::strategy::parse_legs(&["+1", "385p", "-1", "390p", "-1", "390c", "+1", "395c"])
+            .unwrap()
+            .legs;
+        let strategy = Strategy::price(&header, &legs).unwrap();
+
+        let png = build_payoff_chart("SPY", &strategy, header.quote.regular_market_price, Theme::Dark).unwrap();
+        assert_eq!(png[..8], PNG_SIGNATURE);
+    }
+}
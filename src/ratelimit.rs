@@ -0,0 +1,301 @@
+use lazy_static::lazy_static;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Past this many tracked users, channels and guilds, idle ones are dropped.
+const MAX_TRACKED_SCOPES: usize = 10_000;
+
+lazy_static! {
+    static ref LIMITER: Mutex<RateLimiter> = Mutex::new(RateLimiter::new(Limits::from_env()));
+}
+
+/// How much anyone can ask of the bot. The per-minute limits count every
+/// symbol a request fetches. A limit of zero turns that limit off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Mentions past this many in one message are ignored.
+    pub max_symbols: usize,
+    pub user_per_minute: usize,
+    pub channel_per_minute: usize,
+    pub guild_per_minute: usize,
+    /// How long a user who went over a limit is ignored for.
+    pub cooldown: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_symbols: 12,
+            // Enough for one message at `max_symbols`.
+            user_per_minute: 12,
+            channel_per_minute: 40,
+            guild_per_minute: 120,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Limits {
+    /// The default limits, each overridable with a `RATE_LIMIT_*` variable.
+    pub fn from_env() -> Limits {
+        let defaults = Limits::default();
+
+        Limits {
+            max_symbols: env_or("RATE_LIMIT_MAX_SYMBOLS", defaults.max_symbols),
+            user_per_minute: env_or("RATE_LIMIT_USER_PER_MINUTE", defaults.user_per_minute),
+            channel_per_minute: env_or("RATE_LIMIT_CHANNEL_PER_MINUTE", defaults.channel_per_minute),
+            guild_per_minute: env_or("RATE_LIMIT_GUILD_PER_MINUTE", defaults.guild_per_minute),
+            cooldown: Duration::from_secs(env_or("RATE_LIMIT_COOLDOWN_SECS", defaults.cooldown.as_secs())),
+        }
+    }
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Scope {
+    User(u64),
+    Channel(u64),
+    Guild(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Allowed,
+    /// Only the first request turned away gets `notify`, so going over a
+    /// limit doesn't turn into a stream of notices.
+    Limited { retry_after: Duration, notify: bool },
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: Limits,
+    /// When each scope's requests in the last minute were made, oldest first.
+    requests: HashMap<Scope, VecDeque<Instant>>,
+    /// Users who went over their own limit, until when they're ignored.
+    cooldowns: HashMap<u64, Instant>,
+    /// Busy channels and guilds whose requesters were already told so, until
+    /// when they're full.
+    notified: HashMap<Scope, Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: Limits) -> Self {
+        RateLimiter {
+            limits,
+            requests: HashMap::new(),
+            cooldowns: HashMap::new(),
+            notified: HashMap::new(),
+        }
+    }
+
+    /// Charges a request fetching `cost` symbols unless that would take the
+    /// user, their channel or their guild over its limit. A request costing
+    /// more than a limit uses up all of it. Users over their own limit are
+    /// also put on cooldown; a busy channel or guild only holds them up until
+    /// it has room again.
+    pub fn check(&mut self, now: Instant, user: u64, channel: u64, guild: Option<u64>, cost: usize) -> Verdict {
+        if let Some(until) = self.cooldowns.get(&user).copied() {
+            if now < until {
+                return Verdict::Limited {
+                    retry_after: until - now,
+                    notify: false,
+                };
+            }
+            self.cooldowns.remove(&user);
+        }
+
+        let mut scopes = vec![
+            (Scope::User(user), self.limits.user_per_minute),
+            (Scope::Channel(channel), self.limits.channel_per_minute),
+        ];
+        if let Some(guild) = guild {
+            scopes.push((Scope::Guild(guild), self.limits.guild_per_minute));
+        }
+        scopes.retain(|(_, limit)| *limit > 0);
+
+        self.prune(now);
+
+        let mut full = None;
+        for (scope, limit) in &scopes {
+            let times = self.requests.entry(*scope).or_default();
+            while matches!(times.front(), Some(t) if now.duration_since(*t) >= WINDOW) {
+                times.pop_front();
+            }
+            let cost = cost.min(*limit);
+            if times.len() + cost > *limit {
+                let free_at = times[times.len() + cost - limit - 1] + WINDOW;
+                if full.map_or(true, |(_, at)| free_at > at) {
+                    full = Some((*scope, free_at));
+                }
+            }
+        }
+
+        match full {
+            Some((Scope::User(_), free_at)) => {
+                let retry_after = (free_at - now).max(self.limits.cooldown);
+                self.cooldowns.insert(user, now + retry_after);
+                Verdict::Limited {
+                    retry_after,
+                    notify: true,
+                }
+            }
+            Some((scope, free_at)) => {
+                let notify = !matches!(self.notified.get(&scope), Some(until) if now < *until);
+                self.notified.insert(scope, free_at);
+                Verdict::Limited {
+                    retry_after: free_at - now,
+                    notify,
+                }
+            }
+            None => {
+                for (scope, limit) in scopes {
+                    let times = self.requests.entry(scope).or_default();
+                    times.extend(std::iter::repeat(now).take(cost.min(limit)));
+                }
+                Verdict::Allowed
+            }
+        }
+    }
+
+    /// Drops idle scopes and lapsed cooldowns once there are too many to keep.
+    fn prune(&mut self, now: Instant) {
+        if self.requests.len() > MAX_TRACKED_SCOPES {
+            self.requests.retain(|_, times| matches!(times.back(), Some(t) if now.duration_since(*t) < WINDOW));
+        }
+        if self.cooldowns.len() + self.notified.len() > MAX_TRACKED_SCOPES {
+            self.cooldowns.retain(|_, until| now < *until);
+            self.notified.retain(|_, until| now < *until);
+        }
+    }
+}
+
+pub fn check(user: u64, channel: u64, guild: Option<u64>, cost: usize) -> Verdict {
+    LIMITER.lock().unwrap().check(Instant::now(), user, channel, guild, cost)
+}
+
+/// How many symbols one message may mention, with no cap when set to zero.
+pub fn max_symbols() -> usize {
+    match LIMITER.lock().unwrap().limits.max_symbols {
+        0 => usize::MAX,
+        max => max,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits {
+            max_symbols: 12,
+            user_per_minute: 2,
+            channel_per_minute: 3,
+            guild_per_minute: 0,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+
+    #[test]
+    fn it_limits_users_with_a_cooldown() {
+        let mut limiter = RateLimiter::new(limits());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(limiter.check(at(0), 1, 10, None, 1), Verdict::Allowed);
+        assert_eq!(limiter.check(at(1), 1, 10, None, 1), Verdict::Allowed);
+        assert_eq!(
+            limiter.check(at(2), 1, 10, None, 1),
+            Verdict::Limited {
+                retry_after: Duration::from_secs(58),
+                notify: true,
+            }
+        );
+        assert_eq!(
+            limiter.check(at(3), 1, 10, None, 1),
+            Verdict::Limited {
+                retry_after: Duration::from_secs(57),
+                notify: false,
+            }
+        );
+
+        // Other users in the channel aren't held up.
+        assert_eq!(limiter.check(at(3), 2, 10, None, 1), Verdict::Allowed);
+        assert_eq!(limiter.check(at(60), 1, 10, None, 1), Verdict::Allowed);
+    }
+
+    #[test]
+    fn it_limits_busy_channels() {
+        let mut limiter = RateLimiter::new(limits());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        for user in 1..=3 {
+            assert_eq!(limiter.check(at(0), user, 10, Some(100), 1), Verdict::Allowed);
+        }
+        assert_eq!(
+            limiter.check(at(1), 4, 10, Some(100), 1),
+            Verdict::Limited {
+                retry_after: Duration::from_secs(59),
+                notify: true,
+            }
+        );
+        // The channel is only told once while it's full.
+        assert!(matches!(limiter.check(at(2), 5, 10, Some(100), 1), Verdict::Limited { notify: false, .. }));
+
+        // Being turned away by a busy channel isn't the user's fault, so they
+        // aren't held up elsewhere.
+        assert_eq!(limiter.check(at(2), 4, 11, Some(100), 1), Verdict::Allowed);
+        assert_eq!(limiter.check(at(60), 4, 10, Some(100), 1), Verdict::Allowed);
+    }
+
+    #[test]
+    fn it_charges_each_symbol_fetched() {
+        let mut limiter = RateLimiter::new(Limits {
+            user_per_minute: 5,
+            channel_per_minute: 0,
+            ..limits()
+        });
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(limiter.check(at(0), 1, 10, None, 3), Verdict::Allowed);
+        assert_eq!(limiter.check(at(1), 1, 10, None, 2), Verdict::Allowed);
+        assert!(matches!(limiter.check(at(2), 1, 10, None, 1), Verdict::Limited { notify: true, .. }));
+
+        // More symbols than the limit allows still go through once, but use
+        // up the whole minute.
+        assert_eq!(limiter.check(at(0), 2, 10, None, 12), Verdict::Allowed);
+        assert_eq!(
+            limiter.check(at(1), 2, 10, None, 1),
+            Verdict::Limited {
+                retry_after: Duration::from_secs(59),
+                notify: true,
+            }
+        );
+    }
+
+    #[test]
+    fn it_forgets_lapsed_cooldowns() {
+        let mut limiter = RateLimiter::new(Limits {
+            user_per_minute: 1,
+            ..limits()
+        });
+        let start = Instant::now();
+
+        for user in 0..=MAX_TRACKED_SCOPES as u64 {
+            limiter.check(start, user, user, None, 1);
+            limiter.check(start, user, user, None, 1);
+        }
+        assert_eq!(limiter.cooldowns.len(), MAX_TRACKED_SCOPES + 1);
+
+        limiter.check(start + Duration::from_secs(120), u64::MAX, 0, None, 1);
+        assert!(limiter.cooldowns.is_empty());
+    }
+}
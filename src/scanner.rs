@@ -0,0 +1,370 @@
+use crate::api::model::{OptionsContract, OptionsHeader};
+use crate::common::format_large_number;
+use crate::options::{self, ContractKind};
+use crate::stock;
+use crate::symbols;
+use chrono::Utc;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter, CreateMessage};
+use serenity::http::Http;
+use serenity::model::id::ChannelId;
+use std::collections::HashSet;
+use std::env;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const MAX_DIGEST_ROWS: usize = 20;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Flag contracts trading at least this multiple of their open interest.
+    pub volume_oi_ratio: f64,
+    /// Ignore the volume/open interest ratio below this volume.
+    pub min_volume: u64,
+    /// Flag contracts with at least this many dollars traded.
+    pub min_premium: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            volume_oi_ratio: 5.0,
+            min_volume: 500,
+            min_premium: 1_000_000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScannerConfig {
+    pub watchlist: Vec<String>,
+    pub channel_id: u64,
+    pub interval: Duration,
+    pub thresholds: Thresholds,
+}
+
+impl ScannerConfig {
+    /// Reads the scanner configuration from the environment. The scanner is
+    /// disabled unless both `SCANNER_CHANNEL_ID` and `SCANNER_WATCHLIST` are set.
+    /// Watchlist aliases resolve as they do in `$SYMBOL` mentions.
+    pub fn from_env() -> Option<ScannerConfig> {
+        let channel_id = env::var("SCANNER_CHANNEL_ID").ok()?.parse().ok()?;
+        let watchlist = env::var("SCANNER_WATCHLIST")
+            .ok()?
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(symbols::resolve)
+            .collect::<Vec<String>>();
+
+        if watchlist.is_empty() {
+            return None;
+        }
+
+        let defaults = Thresholds::default();
+
+        Some(ScannerConfig {
+            watchlist,
+            channel_id,
+            interval: Duration::from_secs(env_or("SCANNER_INTERVAL_SECS", 60 * 30)),
+            thresholds: Thresholds {
+                volume_oi_ratio: env_or("SCANNER_VOLUME_OI_RATIO", defaults.volume_oi_ratio),
+                min_volume: env_or("SCANNER_MIN_VOLUME", defaults.min_volume),
+                min_premium: env_or("SCANNER_MIN_PREMIUM", defaults.min_premium),
+            },
+        })
+    }
+}
+
+fn env_or<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Symbol, expiration, kind, strike and trading day of a posted contract.
+type PostedKey = (String, i64, ContractKind, u64, i64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnusualContract {
+    pub symbol: String,
+    pub expiration_date: i64,
+    pub kind: ContractKind,
+    pub strike: f64,
+    pub volume: u64,
+    pub open_interest: u64,
+    pub premium: f64,
+}
+
+impl UnusualContract {
+    pub fn volume_oi_ratio(&self) -> Option<f64> {
+        if self.open_interest == 0 {
+            None
+        } else {
+            Some(self.volume as f64 / self.open_interest as f64)
+        }
+    }
+
+    /// Identifies the contract within the session starting at `day`.
+    fn posted_key(&self, day: i64) -> PostedKey {
+        (self.symbol.clone(), self.expiration_date, self.kind, self.strike.to_bits(), day)
+    }
+
+    fn digest_line(&self) -> String {
+        let ratio = self
+            .volume_oi_ratio()
+            .map_or_else(|| "new".to_string(), |r| format!("{:.1}x", r));
+
+        format!(
+            "`{} {} {}{}` vol {} / OI {} ({}) · ${} premium",
+            self.symbol,
+            options::format_expiration(self.expiration_date),
+            self.strike,
+            self.kind,
+            format_large_number(self.volume as f64),
+            format_large_number(self.open_interest as f64),
+            ratio,
+            format_large_number(self.premium),
+        )
+    }
+}
+
+fn is_unusual(contract: &OptionsContract, thresholds: &Thresholds) -> bool {
+    let volume = contract.volume.unwrap_or(0);
+    let open_interest = contract.open_interest.unwrap_or(0);
+
+    let heavy_volume = volume >= thresholds.min_volume
+        && (open_interest == 0
+            || volume as f64 / open_interest as f64 >= thresholds.volume_oi_ratio);
+
+    heavy_volume || options::premium_traded(contract) >= thresholds.min_premium
+}
+
+/// Returns every contract in `header` that crosses one of the `thresholds`.
+pub fn scan_options(symbol: &str, header: &OptionsHeader, thresholds: &Thresholds) -> Vec<UnusualContract> {
+    header
+        .options
+        .iter()
+        .flat_map(|data| {
+            let calls = data.calls.iter().map(move |c| (data.expiration_date, ContractKind::Call, c));
+            let puts = data.puts.iter().map(move |c| (data.expiration_date, ContractKind::Put, c));
+            calls.chain(puts)
+        })
+        .filter(|(_, _, contract)| is_unusual(contract, thresholds))
+        .map(|(expiration_date, kind, contract)| UnusualContract {
+            symbol: symbol.to_string(),
+            expiration_date,
+            kind,
+            strike: contract.strike,
+            volume: contract.volume.unwrap_or(0),
+            open_interest: contract.open_interest.unwrap_or(0),
+            premium: options::premium_traded(contract),
+        })
+        .collect()
+}
+
+async fn scan_watchlist(config: &ScannerConfig) -> Vec<UnusualContract> {
+    let mut flagged = vec![];
+
+    for symbol in &config.watchlist {
+        match options::fetch_option_chain(symbol).await {
+            Ok(chain) => {
+                for header in &chain {
+                    flagged.extend(scan_options(symbol, header, &config.thresholds));
+                }
+            }
+            Err(why) => println!("Error scanning options for {}: {:?}", symbol, why),
+        }
+    }
+
+    flagged.sort_by(|a, b| b.premium.total_cmp(&a.premium));
+    flagged
+}
+
+/// Volume only adds up over the day, so a contract flagged once stays flagged.
+/// Keeps only those not yet posted in the session starting at `day`, and
+/// remembers them; earlier sessions are forgotten.
+fn unposted(flagged: Vec<UnusualContract>, posted: &mut HashSet<PostedKey>, day: i64) -> Vec<UnusualContract> {
+    posted.retain(|key| key.4 == day);
+    flagged
+        .into_iter()
+        .filter(|contract| posted.insert(contract.posted_key(day)))
+        .collect()
+}
+
+pub fn build_digest(flagged: &[UnusualContract]) -> String {
+    let mut lines = flagged
+        .iter()
+        .take(MAX_DIGEST_ROWS)
+        .map(UnusualContract::digest_line)
+        .collect::<Vec<String>>();
+
+    if flagged.len() > MAX_DIGEST_ROWS {
+        lines.push(format!("…and {} more", flagged.len() - MAX_DIGEST_ROWS));
+    }
+
+    lines.join("\n")
+}
+
+async fn post_digest(http: &Http, config: &ScannerConfig, flagged: &[UnusualContract]) {
+    let thresholds = config.thresholds;
+    let embed = CreateEmbed::new()
+        .title("Unusual options activity")
+        .description(build_digest(flagged))
+        .footer(CreateEmbedFooter::new(format!(
+            "vol/OI ≥ {}x (min vol {}) or premium ≥ ${}",
+            thresholds.volume_oi_ratio,
+            thresholds.min_volume,
+            format_large_number(thresholds.min_premium),
+        )));
+    let msg = ChannelId::new(config.channel_id)
+        .send_message(http, CreateMessage::new().embed(embed))
+        .await;
+
+    if let Err(why) = msg {
+        println!("Error sending scanner digest: {:?}", why);
+    }
+}
+
+/// Starts the periodic scanner, which only scans while the market is open.
+/// Only the first call has any effect, so this is safe to call from `ready`,
+/// which fires again on every reconnect.
+pub fn spawn(http: Arc<Http>, config: ScannerConfig) {
+    if STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.interval);
+        let mut posted = HashSet::new();
+
+        loop {
+            interval.tick().await;
+
+            let session = match stock::market_session().await {
+                Ok(session) => session,
+                Err(why) => {
+                    println!("Error checking market hours for the scanner: {}", why);
+                    continue;
+                }
+            };
+            let now = Utc::now().timestamp();
+            if now < session.start || now >= session.end {
+                continue;
+            }
+
+            let flagged = unposted(scan_watchlist(&config).await, &mut posted, session.start);
+            if !flagged.is_empty() {
+                post_digest(&http, &config, &flagged).await;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spy() -> OptionsHeader {
+        options::fixture(include_str!("../fixtures/options_spy.json"))
+    }
+
+    #[test]
+    fn it_flags_volume_over_open_interest() {
+        let thresholds = Thresholds {
+            volume_oi_ratio: 5.0,
+            min_volume: 500,
+            min_premium: f64::MAX,
+        };
+        let flagged = scan_options("SPY", &spy(), &thresholds);
+
+        let found = flagged
+            .iter()
+            .map(|c| (c.kind, c.strike))
+            .collect::<Vec<(ContractKind, f64)>>();
+        assert_eq!(found, [(ContractKind::Call, 390.0), (ContractKind::Put, 395.0)]);
+        assert_eq!(flagged[0].expiration_date, 1616112000);
+        assert_eq!(flagged[0].volume, 25000);
+        assert_eq!(flagged[0].open_interest, 2100);
+    }
+
+    #[test]
+    fn it_skips_low_volume_without_open_interest() {
+        let thresholds = Thresholds {
+            volume_oi_ratio: 5.0,
+            min_volume: 200,
+            min_premium: f64::MAX,
+        };
+        let flagged = scan_options("SPY", &spy(), &thresholds);
+        assert!(flagged.iter().all(|c| c.strike != 400.0));
+
+        let thresholds = Thresholds { min_volume: 100, ..thresholds };
+        let flagged = scan_options("SPY", &spy(), &thresholds);
+        let new_position = flagged.iter().find(|c| c.strike == 400.0).unwrap();
+        assert_eq!(new_position.volume_oi_ratio(), None);
+    }
+
+    #[test]
+    fn it_flags_large_premium() {
+        let thresholds = Thresholds {
+            volume_oi_ratio: f64::MAX,
+            min_volume: u64::MAX,
+            min_premium: 5_000_000.0,
+        };
+        let flagged = scan_options("SPY", &spy(), &thresholds);
+
+        let found = flagged
+            .iter()
+            .map(|c| (c.kind, c.strike, c.premium.round()))
+            .collect::<Vec<(ContractKind, f64, f64)>>();
+        assert_eq!(
+            found,
+            [
+                (ContractKind::Call, 390.0, 14_500_000.0),
+                (ContractKind::Put, 390.0, 5_040_000.0),
+                (ContractKind::Put, 395.0, 5_880_000.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_posts_each_contract_once_a_day() {
+        let thresholds = Thresholds::default();
+        let mut posted = HashSet::new();
+        let (monday, tuesday) = (1613989800, 1614076200);
+
+        let first = unposted(scan_options("SPY", &spy(), &thresholds), &mut posted, monday);
+        assert!(!first.is_empty());
+        assert!(unposted(scan_options("SPY", &spy(), &thresholds), &mut posted, monday).is_empty());
+
+        let next_day = unposted(scan_options("SPY", &spy(), &thresholds), &mut posted, tuesday);
+        assert_eq!(next_day, first);
+        assert_eq!(posted.len(), first.len());
+    }
+
+    #[test]
+    fn it_truncates_the_digest() {
+        let contract = UnusualContract {
+            symbol: "SPY".to_string(),
+            expiration_date: 1616112000,
+            kind: ContractKind::Call,
+            strike: 390.0,
+            volume: 25000,
+            open_interest: 2100,
+            premium: 14_500_000.0,
+        };
+        let flagged = vec![contract; MAX_DIGEST_ROWS + 3];
+        assert_eq!(
+            build_digest(&flagged[..1]),
+            "`SPY 2021-03-19 390C` vol 25.00K / OI 2.10K (11.9x) · $14.50M premium"
+        );
+
+        let digest = build_digest(&flagged);
+        assert_eq!(digest.lines().count(), MAX_DIGEST_ROWS + 1);
+        assert!(digest.ends_with("…and 3 more"));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::storage::{self, Store};
+use crate::theme::Theme;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Chart preferences as saved before the database, one per line as
+/// `guild <id> <theme>` or `user <id> <theme>`. Only read now, to carry them
+/// over into the database once.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    guild_themes: HashMap<u64, Theme>,
+    user_themes: HashMap<u64, Theme>,
+}
+
+impl Settings {
+    /// Reads the settings file, skipping any line it can't make sense of
+    /// rather than losing the rest.
+    pub fn parse(contents: &str) -> Settings {
+        let mut settings = Settings::default();
+
+        for line in contents.lines() {
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            let (scope, id, theme) = match fields[..] {
+                [scope, id, theme] => (scope, id, theme),
+                _ => continue,
+            };
+            let (id, theme) = match (id.parse(), theme.parse()) {
+                (Ok(id), Ok(theme)) => (id, theme),
+                _ => continue,
+            };
+
+            match scope {
+                "guild" => settings.guild_themes.insert(id, theme),
+                "user" => settings.user_themes.insert(id, theme),
+                _ => continue,
+            };
+        }
+
+        settings
+    }
+
+    /// A missing file just means there's nothing saved.
+    fn load(path: &Path) -> io::Result<Settings> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Settings::parse(&contents)),
+            Err(why) if why.kind() == io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(why) => Err(why),
+        }
+    }
+
+    /// Copies every theme into the store, returning how many there were.
+    fn import(&self, store: &mut dyn Store) -> storage::Result<usize> {
+        for (id, theme) in &self.guild_themes {
+            store.set_guild_theme(*id, Some(*theme))?;
+        }
+        for (id, theme) in &self.user_themes {
+            store.set_user_theme(*id, Some(*theme))?;
+        }
+        Ok(self.guild_themes.len() + self.user_themes.len())
+    }
+}
+
+pub fn settings_path() -> PathBuf {
+    env::var("STOUT_SETTINGS_PATH")
+        .unwrap_or_else(|_| "settings.txt".to_string())
+        .into()
+}
+
+/// Carries themes saved to `path` over into the store. The file is left in
+/// place, but nothing reads it again.
+pub fn import_legacy(store: &mut dyn Store, path: &Path) -> storage::Result<()> {
+    let settings = Settings::load(path).map_err(|why| {
+        println!("Error reading settings from {}: {:?}", path.display(), why);
+        storage::Error::InvalidPath(path.to_path_buf())
+    })?;
+    let count = settings.import(store)?;
+    if count > 0 {
+        println!("Imported {} theme(s) from {}", count, path.display());
+    }
+    Ok(())
+}
+
+/// A user's own theme wins over their guild's.
+fn stored_theme(store: &dyn Store, guild_id: Option<u64>, user_id: u64) -> storage::Result<Theme> {
+    let guild_theme = || guild_id.map_or(Ok(None), |id| store.guild_theme(id));
+    let theme = match store.user_theme(user_id)? {
+        Some(theme) => Some(theme),
+        None => guild_theme()?,
+    };
+    Ok(theme.unwrap_or_default())
+}
+
+pub async fn theme_for(guild_id: Option<u64>, user_id: u64) -> Theme {
+    match storage::with_store(move |store| stored_theme(store, guild_id, user_id)).await {
+        Ok(theme) => theme,
+        Err(why) => {
+            println!("Error reading theme for {}: {:?}", user_id, why);
+            Theme::default()
+        }
+    }
+}
+
+/// Sets or, with `None`, clears a user's theme.
+pub async fn set_user_theme(user_id: u64, theme: Option<Theme>) -> storage::Result<()> {
+    storage::with_store(move |store| store.set_user_theme(user_id, theme)).await
+}
+
+/// Sets or, with `None`, clears a guild's theme.
+pub async fn set_guild_theme(guild_id: u64, theme: Option<Theme>) -> storage::Result<()> {
+    storage::with_store(move |store| store.set_guild_theme(guild_id, theme)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::MemoryStore;
+
+    #[test]
+    fn it_prefers_the_user_theme() {
+        let mut store = MemoryStore::default();
+        assert_eq!(stored_theme(&store, Some(1), 2), Ok(Theme::Light));
+
+        store.set_guild_theme(1, Some(Theme::Dark)).unwrap();
+        assert_eq!(stored_theme(&store, Some(1), 2), Ok(Theme::Dark));
+        assert_eq!(stored_theme(&store, None, 2), Ok(Theme::Light));
+
+        store.set_user_theme(2, Some(Theme::Colorblind)).unwrap();
+        assert_eq!(stored_theme(&store, Some(1), 2), Ok(Theme::Colorblind));
+
+        store.set_user_theme(2, None).unwrap();
+        assert_eq!(stored_theme(&store, Some(1), 2), Ok(Theme::Dark));
+    }
+
+    #[test]
+    fn it_imports_saved_settings() {
+        let saved = "guild 10 dark\nuser 20 colorblind-dark\nuser 3 light\nuser twenty dark\nchannel 5 dark\nuser 7 neon\n";
+        let mut store = MemoryStore::default();
+
+        assert_eq!(Settings::parse(saved).import(&mut store), Ok(3));
+        assert_eq!(store.guild_theme(10), Ok(Some(Theme::Dark)));
+        assert_eq!(store.user_theme(20), Ok(Some(Theme::ColorblindDark)));
+        assert_eq!(store.user_theme(3), Ok(Some(Theme::Light)));
+        assert_eq!(store.user_theme(7), Ok(None));
+    }
+
+    #[test]
+    fn it_loads_from_disk() {
+        let path = env::temp_dir().join(format!("stout-settings-{}.txt", std::process::id()));
+        fs::write(&path, "user 42 dark").unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), Settings::parse("user 42 dark"));
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(Settings::load(&path).unwrap(), Settings::default());
+    }
+}
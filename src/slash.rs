@@ -0,0 +1,379 @@
+use crate::api::model::SearchQuote;
+use crate::common::TimeFrame;
+use crate::plot::{self, ChartType, Indicator};
+use crate::ratelimit::{self, Verdict};
+use crate::{settings, stock};
+use serenity::builder::{
+    CreateAttachment, CreateAutocompleteResponse, CreateCommand, CreateCommandOption, CreateEmbed,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+};
+use serenity::client::Context;
+use serenity::http::Http;
+use serenity::model::application::{Command, CommandData, CommandInteraction, CommandOptionType, Interaction, InteractionContext};
+use serenity::model::id::GuildId;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const CHART_TYPES: [&str; 3] = ["line", "candle", "ohlc"];
+const PRICE_SCALES: [&str; 3] = ["linear", "log", "pct"];
+/// How many symbols to suggest while one is being typed.
+const MAX_SUGGESTIONS: usize = 10;
+/// Discord cuts off longer suggestion names.
+const MAX_SUGGESTION_CHARS: usize = 100;
+
+static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// The slash command versions of `~quote`, `~chart` and `~info`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlashCommand {
+    Quote {
+        symbol: String,
+    },
+    Chart {
+        symbol: String,
+        time_frame: TimeFrame,
+        options: plot::ChartOptions,
+    },
+    Info {
+        symbol: String,
+    },
+}
+
+impl SlashCommand {
+    pub fn parse(data: &CommandData) -> Result<SlashCommand, String> {
+        let option = |name: &str| {
+            data.options
+                .iter()
+                .find(|o| o.name == name)
+                .and_then(|o| o.value.as_str())
+        };
+        let symbol = option("symbol")
+            .map(|symbol| symbol.trim_start_matches('$').to_uppercase())
+            .filter(|symbol| !symbol.is_empty())
+            .ok_or("A symbol is required")?;
+
+        match data.name.as_str() {
+            "quote" => Ok(SlashCommand::Quote { symbol }),
+            "chart" => {
+                let time_frame = option("timeframe").map(str::parse).transpose()?.unwrap_or(TimeFrame::Day1);
+                let defaults = plot::ChartOptions::for_time_frame(time_frame);
+
+                let mut indicators = vec![];
+                for word in option("indicators").unwrap_or_default().split_whitespace() {
+                    let indicator = word.parse::<Indicator>()?;
+                    if !indicators.contains(&indicator) {
+                        indicators.push(indicator);
+                    }
+                }
+
+                let options = plot::ChartOptions {
+                    chart_type: option("type").map(str::parse).transpose()?.unwrap_or(ChartType::Line),
+                    price_scale: option("scale").map(str::parse).transpose()?.unwrap_or(defaults.price_scale),
+                    indicators,
+                    ..defaults
+                };
+                Ok(SlashCommand::Chart {
+                    symbol,
+                    time_frame,
+                    options,
+                })
+            }
+            "info" => Ok(SlashCommand::Info { symbol }),
+            name => Err(format!("Unknown command `/{}`", name)),
+        }
+    }
+}
+
+/// The commands as Discord's API expects them. Symbols are suggested as
+/// they're typed; time frames, chart types and scales are offered as
+/// choices, so they can't be mistyped. They're only offered in servers.
+pub fn definitions() -> Vec<CreateCommand> {
+    let symbol = CreateCommandOption::new(CommandOptionType::String, "symbol", "Ticker, e.g. AAPL")
+        .required(true)
+        .set_autocomplete(true);
+    let choices = |option: CreateCommandOption, names: &[&str]| {
+        names.iter().fold(option, |option, name| option.add_string_choice(*name, *name))
+    };
+    let time_frames = TimeFrame::ALL.iter().map(|t| t.as_str()).collect::<Vec<&str>>();
+
+    let chart_options = vec![
+        symbol.clone(),
+        choices(
+            CreateCommandOption::new(CommandOptionType::String, "timeframe", "Time frame, 1D by default"),
+            &time_frames,
+        ),
+        choices(
+            CreateCommandOption::new(CommandOptionType::String, "type", "Chart type, line by default"),
+            &CHART_TYPES,
+        ),
+        choices(
+            CreateCommandOption::new(CommandOptionType::String, "scale", "Price scale, log for 5Y and linear otherwise"),
+            &PRICE_SCALES,
+        ),
+        CreateCommandOption::new(CommandOptionType::String, "indicators", "e.g. sma50 bb rsi"),
+    ];
+
+    vec![
+        CreateCommand::new("quote")
+            .description("Price, change and day range without a chart")
+            .add_option(symbol.clone()),
+        CreateCommand::new("chart")
+            .description("Quote and chart")
+            .set_options(chart_options),
+        CreateCommand::new("info")
+            .description("Company profile: sector, industry, size and what the business does")
+            .add_option(symbol),
+    ]
+    .into_iter()
+    .map(|command| command.contexts(vec![InteractionContext::Guild]))
+    .collect()
+}
+
+/// Registers the commands globally, which can take up to an hour to reach
+/// every server. With `SLASH_COMMANDS_GUILD_ID` set they're registered to that
+/// server only, where they show up immediately. Only the first successful
+/// call has any effect, so this is safe to call from `ready`.
+pub async fn register(http: &Http) {
+    if REGISTERED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let guild_id = env::var("SLASH_COMMANDS_GUILD_ID").ok().and_then(|id| id.parse().ok());
+    let registered = match guild_id {
+        Some(guild_id) => GuildId::new(guild_id).set_commands(http, definitions()).await,
+        None => Command::set_global_commands(http, definitions()).await,
+    };
+    if let Err(why) = registered {
+        println!("Error registering slash commands: {:?}", why);
+        REGISTERED.store(false, Ordering::SeqCst);
+    }
+}
+
+pub async fn handle(context: &Context, interaction: Interaction) {
+    match interaction {
+        Interaction::Command(interaction) => run(context, &interaction).await,
+        Interaction::Autocomplete(interaction) => suggest(context, &interaction).await,
+        _ => {}
+    }
+}
+
+async fn run(context: &Context, interaction: &CommandInteraction) {
+    let verdict = ratelimit::check(
+        interaction.user.id.get(),
+        interaction.channel_id.get(),
+        interaction.guild_id.map(|id| id.get()),
+        1,
+    );
+    if let Verdict::Limited { retry_after, .. } = verdict {
+        // Only the user sees the notice, so there's no spam in telling them
+        // every time.
+        let notice = CreateInteractionResponseMessage::new()
+            .content(format!("That's a lot of requests, try again in {}s.", retry_after.as_secs().max(1)))
+            .ephemeral(true);
+        if let Err(why) = interaction
+            .create_response(&context.http, CreateInteractionResponse::Message(notice))
+            .await
+        {
+            println!("Error responding to interaction: {:?}", why);
+        }
+        return;
+    }
+
+    // Fetching and charting can take longer than the three seconds Discord
+    // allows for a response, so the user sees a loading state until the
+    // follow-up arrives.
+    if let Err(why) = interaction.defer(&context.http).await {
+        println!("Error acknowledging interaction: {:?}", why);
+        return;
+    }
+
+    let followup = match SlashCommand::parse(&interaction.data) {
+        Ok(command) => {
+            let (embed, chart) = reply(interaction, command).await;
+            CreateInteractionResponseFollowup::new().embed(embed).files(chart)
+        }
+        Err(why) => CreateInteractionResponseFollowup::new().content(why),
+    };
+
+    if let Err(why) = interaction.create_followup(&context.http, followup).await {
+        println!("Error sending follow-up: {:?}", why);
+    }
+}
+
+/// Labels search results like `AAPL - Apple Inc. (NASDAQ)`, with the symbol
+/// as the value filled in.
+fn suggestions(quotes: Vec<SearchQuote>) -> Vec<(String, String)> {
+    quotes
+        .into_iter()
+        .map(|quote| {
+            let mut name = quote.symbol.clone();
+            if let Some(company) = quote.longname.or(quote.shortname) {
+                name = format!("{} - {}", name, company);
+            }
+            if let Some(exchange) = quote.exchange {
+                name = format!("{} ({})", name, exchange);
+            }
+            (name.chars().take(MAX_SUGGESTION_CHARS).collect(), quote.symbol)
+        })
+        .collect()
+}
+
+/// Suggests symbols matching what's been typed into the `symbol` option.
+async fn suggest(context: &Context, interaction: &CommandInteraction) {
+    let typed = match interaction.data.autocomplete() {
+        Some(option) if option.name == "symbol" => option.value.trim().trim_start_matches('$').to_string(),
+        _ => return,
+    };
+
+    let quotes = if typed.is_empty() {
+        vec![]
+    } else {
+        crate::CLIENT.search(&typed, MAX_SUGGESTIONS).await.unwrap_or_else(|why| {
+            println!("Error searching for {}: {:?}", typed, why);
+            vec![]
+        })
+    };
+    let response = suggestions(quotes)
+        .into_iter()
+        .fold(CreateAutocompleteResponse::new(), |response, (name, symbol)| {
+            response.add_string_choice(name, symbol)
+        });
+
+    if let Err(why) = interaction
+        .create_response(&context.http, CreateInteractionResponse::Autocomplete(response))
+        .await
+    {
+        println!("Error suggesting symbols: {:?}", why);
+    }
+}
+
+async fn reply(interaction: &CommandInteraction, command: SlashCommand) -> (CreateEmbed, Option<CreateAttachment>) {
+    match command {
+        SlashCommand::Quote { symbol } => match stock::Stock::new(&symbol, TimeFrame::Day1).await {
+            Ok(stock) => (crate::quote_embed(&stock), None),
+            Err(why) => (CreateEmbed::new().description(why), None),
+        },
+        SlashCommand::Chart {
+            symbol,
+            time_frame,
+            options,
+        } => {
+            let stock = match stock::Stock::new(&symbol, time_frame).await {
+                Ok(stock) => stock,
+                Err(why) => return (CreateEmbed::new().description(why), None),
+            };
+            let options = plot::ChartOptions {
+                theme: settings::theme_for(interaction.guild_id.map(|id| id.get()), interaction.user.id.get()).await,
+                ..options
+            };
+            let filename = options.format.filename("chart");
+            let embed = crate::quote_embed(&stock);
+
+            match plot::build_chart(&stock, options) {
+                Ok(chart) => (embed.attachment(&filename), Some(CreateAttachment::bytes(chart, filename))),
+                Err(why) => {
+                    println!("Error rendering chart for {}: {:?}", stock.symbol, why);
+                    (embed, None)
+                }
+            }
+        }
+        SlashCommand::Info { symbol: requested } => {
+            let symbol = crate::symbols::resolve(&requested);
+            match crate::CLIENT.get_company_data(&symbol).await {
+                Ok(company) => (crate::company_embed(&symbol, company), None),
+                Err(why) => {
+                    println!("Error getting company data for {}: {:?}", symbol, why);
+                    let embed = CreateEmbed::new().description(stock::fetch_error(&requested, &stock::io_error(why)));
+                    (embed, None)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn command_data(name: &str, options: Value) -> CommandData {
+        let options = options
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| json!({ "name": o[0], "type": 3, "value": o[1] }))
+            .collect::<Vec<Value>>();
+        serde_json::from_value(json!({ "id": "1", "name": name, "type": 1, "options": options })).unwrap()
+    }
+
+    #[test]
+    fn it_parses_slash_commands() {
+        let data = command_data(
+            "chart",
+            json!([["symbol", "$tsla"], ["timeframe", "3M"], ["type", "candle"], ["scale", "pct"], ["indicators", "sma50 rsi sma50"]]),
+        );
+        let options = plot::ChartOptions {
+            chart_type: ChartType::Candle,
+            price_scale: plot::PriceScale::Percent,
+            indicators: vec![Indicator::Sma(50), Indicator::Rsi(14)],
+            ..plot::ChartOptions::for_time_frame(TimeFrame::Month3)
+        };
+        assert_eq!(
+            SlashCommand::parse(&data),
+            Ok(SlashCommand::Chart {
+                symbol: "TSLA".to_string(),
+                time_frame: TimeFrame::Month3,
+                options,
+            })
+        );
+
+        let data = command_data("chart", json!([["symbol", "SPY"], ["timeframe", "5Y"]]));
+        assert_eq!(
+            SlashCommand::parse(&data),
+            Ok(SlashCommand::Chart {
+                symbol: "SPY".to_string(),
+                time_frame: TimeFrame::Year5,
+                options: plot::ChartOptions::for_time_frame(TimeFrame::Year5),
+            })
+        );
+
+        let data = command_data("chart", json!([["symbol", "SPY"], ["indicators", "sma50 fib"]]));
+        assert!(SlashCommand::parse(&data).is_err());
+
+        let data = command_data("quote", json!([["symbol", "$"]]));
+        assert!(SlashCommand::parse(&data).is_err());
+    }
+
+    #[test]
+    fn it_offers_choices_that_parse() {
+        let definitions = serde_json::to_value(definitions()).unwrap();
+        let chart = definitions.as_array().unwrap().iter().find(|d| d["name"] == "chart").unwrap();
+
+        for option in chart["options"].as_array().unwrap() {
+            for choice in option["choices"].as_array().into_iter().flatten() {
+                let data = command_data("chart", json!([["symbol", "SPY"], [option["name"], choice["value"]]]));
+                assert!(SlashCommand::parse(&data).is_ok(), "{}", choice);
+            }
+        }
+    }
+
+    #[test]
+    fn it_labels_suggestions() {
+        let quote = |symbol: &str, longname: Option<&str>, exchange: Option<&str>| SearchQuote {
+            symbol: symbol.to_string(),
+            shortname: Some("Short".to_string()),
+            longname: longname.map(str::to_string),
+            quote_type: Some("EQUITY".to_string()),
+            exchange: exchange.map(str::to_string),
+        };
+
+        let suggested = suggestions(vec![
+            quote("AAPL", Some("Apple Inc."), Some("NASDAQ")),
+            quote("APC.F", None, None),
+            quote("LONG", Some(&"x".repeat(200)), Some("NYSE")),
+        ]);
+        assert_eq!(suggested[0], ("AAPL - Apple Inc. (NASDAQ)".to_string(), "AAPL".to_string()));
+        assert_eq!(suggested[1], ("APC.F - Short".to_string(), "APC.F".to_string()));
+        assert_eq!(suggested[2].0.chars().count(), MAX_SUGGESTION_CHARS);
+    }
+}
@@ -1,8 +1,14 @@
-use crate::api::model::{ChartMeta, CompanyProfile, CompanyMarketCap};
+use crate::api::model::{ChartCurrentTradingPeriod, ChartMeta, ChartTradingPeriod, CompanyData, CompanyProfile};
+use crate::api::{Interval, Range};
 use crate::common::{chart_data_to_prices, Price, TimeFrame};
+use crate::events::{chart_events, Event};
+use crate::symbols::{self, Contract, Instrument};
 use std::io;
 use tokio::task;
 
+/// Whose sessions stand in for the US market's.
+const MARKET_SYMBOL: &str = "SPY";
+
 #[derive(Debug)]
 pub struct Stock {
     pub high: f64,
@@ -14,87 +20,158 @@ pub struct Stock {
     pub pct_change: f64,
     pub company: Option<CompanyProfile>,
     pub market_cap: Option<f64>,
+    pub instrument: Instrument,
+    pub currency: Option<String>,
+    /// Only set for futures.
+    pub contract: Option<Contract>,
+    /// Only set for intraday charts.
+    pub previous_close: Option<f64>,
+    /// Today's pre, regular and post market sessions, only set for intraday charts.
+    pub trading_periods: Option<ChartCurrentTradingPeriod>,
+    /// Earnings, dividends and splits within the time frame.
+    pub events: Vec<Event>,
 }
 
 impl Stock {
-    pub async fn new(symbol: &str) -> Stock {
-        let (company_profile, market_cap, current_regular_price, current_post_price, _volume) =
-            fetch_current_price(symbol.to_string())
-                .await
-                .unwrap()
-                .unwrap();
-        let (time_frame, chart_data, prices) = fetch_symbol_data(symbol.to_string())
-            .await
-            .unwrap()
-            .unwrap();
+    /// Fetches a symbol's quote and chart together. Fails with a message
+    /// fit for the user when either can't be fetched.
+    pub async fn new(requested: &str, time_frame: TimeFrame) -> Result<Stock, String> {
+        let symbol = symbols::resolve(requested);
+        let (company, chart) = tokio::join!(
+            fetch_current_price(symbol.clone()),
+            fetch_symbol_data(symbol.clone(), time_frame),
+        );
+        let failed = |why: io::Error| {
+            println!("Error fetching {}: {:?}", symbol, why);
+            fetch_error(requested, &why)
+        };
+        let joined = |why: task::JoinError| failed(io::Error::other(why));
+        let company = company.map_err(joined)?.map_err(failed)?;
+        let SymbolData { time_frame, meta: chart_data, prices, events } = chart.map_err(joined)?.map_err(failed)?;
+
+        let quote_type = company.price.quote_type.as_deref().or(chart_data.instrument_type.as_deref());
+        let instrument = quote_type.map_or(Instrument::Equity, Instrument::from_quote_type);
+        let contract = if instrument == Instrument::Future {
+            let detail = company.summary_detail.as_ref();
+            Some(Contract {
+                name: company.price.short_name.clone(),
+                expires: detail.and_then(|d| d.expire_date.as_ref()).and_then(|d| d.fmt.clone()),
+                open_interest: detail.and_then(|d| d.open_interest.as_ref()).and_then(|i| i.amount),
+            })
+        } else {
+            None
+        };
 
         
-        let prev_close_price = if time_frame == TimeFrame::Day1 {
-            Some(chart_data.chart_previous_close)
-        } else { None };
-
-        let current_price = get_current_price(current_regular_price, current_post_price);
+        let (prev_close_price, trading_periods) = if time_frame == TimeFrame::Day1 {
+            (Some(chart_data.chart_previous_close), chart_data.current_trading_period)
+        } else { (None, None) };
+
+        let current_price = get_current_price(
+            company.price.regular_market_price.price,
+            company.price.post_market_price.price,
+        );
         let (high, low) = get_high_low(current_price, &prices);
         let pct_change = pct_change(current_price, time_frame, prev_close_price, &prices);
 
-        Stock {
-            symbol: symbol.to_string(),
-            company: company_profile,
-            market_cap: market_cap.map_or_else(|| None, |v| v.amount),
+        Ok(Stock {
+            symbol,
+            company: company.profile,
+            market_cap: company.price.market_cap.and_then(|v| v.amount),
+            instrument,
+            currency: company.price.currency,
+            contract,
             prices,
             high,
             low,
             current_price,
             time_frame,
             pct_change,
-        }
+            previous_close: prev_close_price,
+            trading_periods,
+            events,
+        })
     }
 }
 
+/// The US market's regular session: today's, or outside of one the most
+/// recent or next, as Yahoo has it. Unlike `MarketHours` this follows
+/// daylight saving time and skips holidays.
+pub async fn market_session() -> Result<ChartTradingPeriod, String> {
+    let chart = crate::CLIENT
+        .get_chart_data(MARKET_SYMBOL, Interval::Day1, Range::Day1, false)
+        .await
+        .map_err(|why| format!("Couldn't get market hours: {:?}", why))?;
+
+    chart
+        .meta
+        .current_trading_period
+        .map(|periods| periods.regular)
+        .ok_or_else(|| "Yahoo didn't report market hours".to_string())
+}
+
+/// What to tell the user when fetching `requested`, spelled as they typed
+/// it, failed: either Yahoo doesn't know it or couldn't be reached.
+pub fn fetch_error(requested: &str, why: &io::Error) -> String {
+    let requested = requested.trim_start_matches('$');
+    if why.kind() == io::ErrorKind::NotFound {
+        format!("Couldn't find ${}", requested)
+    } else {
+        format!("Couldn't get data for ${} right now, try again in a bit", requested)
+    }
+}
+
+/// Keeps whether Yahoo didn't know the symbol, for `fetch_error`.
+pub fn io_error(err: anyhow::Error) -> io::Error {
+    let kind = if err.is::<crate::api::NotFound>() {
+        io::ErrorKind::NotFound
+    } else {
+        io::ErrorKind::Other
+    };
+    io::Error::new(kind, err)
+}
+
 fn remove_zeros_lows(prices: Vec<Price>) -> Vec<Price> {
     prices.into_iter().filter(|x| x.low.ne(&0.0)).collect()
 }
 
-fn fetch_current_price(
-    symbol: String,
-) -> task::JoinHandle<Result<(Option<CompanyProfile>, Option<CompanyMarketCap>, f64, Option<f64>, String), io::Error>> {
+fn fetch_current_price(symbol: String) -> task::JoinHandle<Result<CompanyData, io::Error>> {
     task::spawn(async move {
-        let response = crate::CLIENT.get_company_data(&symbol).await;
-
-        match response {
-            Ok(payload) => Ok((
-                payload.profile,
-                payload.price.market_cap,
-                payload.price.regular_market_price.price,
-                payload.price.post_market_price.price,
-                payload.price.regular_market_volume.fmt,
-            )),
-            Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
-        }
+        crate::CLIENT
+            .get_company_data(&symbol)
+            .await
+            .map_err(io_error)
     })
 }
 
-fn fetch_symbol_data(
-    symbol: String,
-) -> task::JoinHandle<Result<(TimeFrame, ChartMeta, Vec<Price>), io::Error>> {
+/// A symbol's bars over a time frame, with the events to mark on them.
+struct SymbolData {
+    time_frame: TimeFrame,
+    meta: ChartMeta,
+    prices: Vec<Price>,
+    events: Vec<Event>,
+}
+
+fn fetch_symbol_data(symbol: String, time_frame: TimeFrame) -> task::JoinHandle<Result<SymbolData, io::Error>> {
     task::spawn(async move {
-        let time_frame = TimeFrame::Day1;
-        let response = crate::CLIENT
-            .get_chart_data(
-                &symbol,
-                time_frame.api_interval(),
-                time_frame.as_range(),
-                true,
-            )
-            .await;
+        let (interval, range) = (time_frame.api_interval(), time_frame.as_range());
+        let response = if time_frame.shows_events() {
+            crate::CLIENT.get_chart_data_with_events(&symbol, interval, range, true).await
+        } else {
+            crate::CLIENT.get_chart_data(&symbol, interval, range, true).await
+        };
 
         match response {
-            Ok(payload) => Ok((
-                time_frame,
-                payload.meta.clone(),
-                chart_data_to_prices(payload),
-            )),
-            Err(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+            Ok(payload) => {
+                let events = payload.events.as_ref().map(chart_events).unwrap_or_default();
+                Ok(SymbolData {
+                    time_frame,
+                    meta: payload.meta.clone(),
+                    prices: chart_data_to_prices(payload),
+                    events,
+                })
+            }
+            Err(err) => Err(io_error(err)),
         }
     })
 }
@@ -152,3 +229,20 @@ pub fn get_high_low(current_price: f64, data: &[Price]) -> (f64, f64) {
 
     (max, min)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_tells_missing_symbols_from_outages() {
+        let missing = io_error(crate::api::NotFound("BTC-USD".to_string()).into());
+        assert_eq!(fetch_error("$btc", &missing), "Couldn't find $btc");
+
+        let outage = io_error(anyhow::anyhow!("Failed to get request"));
+        assert_eq!(
+            fetch_error("BTC", &outage),
+            "Couldn't get data for $BTC right now, try again in a bit"
+        );
+    }
+}
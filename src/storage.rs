@@ -0,0 +1,655 @@
+use crate::settings;
+use crate::theme::Theme;
+use rusqlite::{params, Connection, OptionalExtension, Row, NO_PARAMS};
+#[cfg(test)]
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use tokio::task;
+
+pub use rusqlite::Error;
+pub type Result<T> = std::result::Result<T, Error>;
+
+static STORE: OnceLock<Mutex<Box<dyn Store>>> = OnceLock::new();
+
+/// A change to the database, applied once.
+enum Migration {
+    Sql(&'static str),
+    /// Carries over themes from the settings file that predates the database.
+    ImportSettings,
+}
+
+/// Applied in order, each in its own transaction. `PRAGMA user_version`
+/// records how many have run, so new ones only ever go on the end, and one
+/// that fails is tried again on the next start.
+const MIGRATIONS: [Migration; 2] = [
+    Migration::Sql(
+        r#"
+    CREATE TABLE guilds (
+        id INTEGER PRIMARY KEY,
+        theme TEXT
+    );
+    CREATE TABLE users (
+        id INTEGER PRIMARY KEY,
+        theme TEXT
+    );
+    CREATE TABLE watchlists (
+        user_id INTEGER NOT NULL,
+        symbol TEXT NOT NULL,
+        PRIMARY KEY (user_id, symbol)
+    );
+    CREATE TABLE alerts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        user_id INTEGER NOT NULL,
+        channel_id INTEGER NOT NULL,
+        symbol TEXT NOT NULL,
+        direction TEXT NOT NULL,
+        price REAL NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX alerts_by_user ON alerts (user_id);
+    CREATE INDEX alerts_by_symbol ON alerts (symbol);
+    CREATE TABLE positions (
+        user_id INTEGER NOT NULL,
+        symbol TEXT NOT NULL,
+        quantity REAL NOT NULL,
+        cost_basis REAL NOT NULL,
+        PRIMARY KEY (user_id, symbol)
+    );
+"#,
+    ),
+    Migration::ImportSettings,
+];
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Above,
+    Below,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Direction::Above => write!(f, "above"),
+            Direction::Below => write!(f, "below"),
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "above" | ">" => Ok(Direction::Above),
+            "below" | "<" => Ok(Direction::Below),
+            _ => Err("Valid directions are: 'above', 'below'"),
+        }
+    }
+}
+
+/// A request to be told when a symbol crosses a price.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// Assigned by the store; ignored when adding an alert.
+    pub id: i64,
+    pub user_id: u64,
+    /// Where to post once the alert triggers.
+    pub channel_id: u64,
+    pub symbol: String,
+    pub direction: Direction,
+    pub price: f64,
+    pub created_at: i64,
+}
+
+#[allow(dead_code)]
+impl Alert {
+    pub fn is_triggered(&self, price: f64) -> bool {
+        match self.direction {
+            Direction::Above => price >= self.price,
+            Direction::Below => price <= self.price,
+        }
+    }
+}
+
+/// A holding in a user's portfolio.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub symbol: String,
+    pub quantity: f64,
+    /// Average price paid per share.
+    pub cost_basis: f64,
+}
+
+/// Everything the bot remembers between restarts. Symbols are stored as
+/// given, so callers should resolve aliases first. Only themes have commands
+/// so far; watchlists, alerts and portfolios are here for the ones to come.
+#[allow(dead_code)]
+pub trait Store: Send {
+    fn guild_theme(&self, guild_id: u64) -> Result<Option<Theme>>;
+    /// Sets or, with `None`, clears a guild's default theme.
+    fn set_guild_theme(&mut self, guild_id: u64, theme: Option<Theme>) -> Result<()>;
+    fn user_theme(&self, user_id: u64) -> Result<Option<Theme>>;
+    /// Sets or, with `None`, clears a user's theme.
+    fn set_user_theme(&mut self, user_id: u64, theme: Option<Theme>) -> Result<()>;
+
+    /// A user's watched symbols, in the order they were added.
+    fn watchlist(&self, user_id: u64) -> Result<Vec<String>>;
+    /// Whether the symbol wasn't already on the watchlist.
+    fn watch(&mut self, user_id: u64, symbol: &str) -> Result<bool>;
+    /// Whether the symbol was on the watchlist.
+    fn unwatch(&mut self, user_id: u64, symbol: &str) -> Result<bool>;
+
+    fn alerts(&self, user_id: u64) -> Result<Vec<Alert>>;
+    /// Every user's alerts on a symbol, for checking them against its price.
+    fn alerts_for_symbol(&self, symbol: &str) -> Result<Vec<Alert>>;
+    /// Returns the new alert's id.
+    fn add_alert(&mut self, alert: &Alert) -> Result<i64>;
+    /// Whether the user had an alert with that id.
+    fn remove_alert(&mut self, user_id: u64, id: i64) -> Result<bool>;
+
+    /// A user's positions, by symbol.
+    fn positions(&self, user_id: u64) -> Result<Vec<Position>>;
+    /// Adds the position, or replaces the one already held in the symbol.
+    fn set_position(&mut self, user_id: u64, position: &Position) -> Result<()>;
+    /// Whether the user held the symbol.
+    fn remove_position(&mut self, user_id: u64, symbol: &str) -> Result<bool>;
+}
+
+/// SQLite stores integers signed; Discord ids fit, as snowflakes stay below 2^63.
+fn to_sql_id(id: u64) -> i64 {
+    id as i64
+}
+
+#[allow(dead_code)]
+fn from_sql_id(id: i64) -> u64 {
+    id as u64
+}
+
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Opens the database, creating it if need be, and runs any pending
+    /// migrations, importing themes from `settings_path` if that's still to do.
+    pub fn open(path: &Path, settings_path: &Path) -> Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open(path)?, settings_path)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> Result<SqliteStore> {
+        SqliteStore::with_connection(Connection::open_in_memory()?, Path::new(""))
+    }
+
+    fn with_connection(conn: Connection, settings_path: &Path) -> Result<SqliteStore> {
+        let mut store = SqliteStore { conn };
+        let applied = store.migrate(settings_path)?;
+        if applied > 0 {
+            println!("Applied {} database migration(s)", applied);
+        }
+        Ok(store)
+    }
+
+    /// Brings the database up to date, returning how many migrations ran.
+    fn migrate(&mut self, settings_path: &Path) -> Result<usize> {
+        let version: i64 = self.conn.query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))?;
+        let pending = MIGRATIONS.iter().enumerate().skip(version as usize);
+
+        let mut applied = 0;
+        for (idx, migration) in pending {
+            self.conn.execute_batch("BEGIN")?;
+            let migrated = match migration {
+                Migration::Sql(sql) => self.conn.execute_batch(sql),
+                Migration::ImportSettings => settings::import_legacy(self, settings_path),
+            }
+            .and_then(|()| self.conn.execute_batch(&format!("PRAGMA user_version = {}", idx + 1)));
+
+            if let Err(why) = migrated {
+                self.conn.execute_batch("ROLLBACK")?;
+                return Err(why);
+            }
+            self.conn.execute_batch("COMMIT")?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    fn theme(&self, table: &str, id: u64) -> Result<Option<Theme>> {
+        let theme: Option<Option<String>> = self
+            .conn
+            .query_row(
+                &format!("SELECT theme FROM {} WHERE id = ?", table),
+                params![to_sql_id(id)],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(theme.flatten().and_then(|theme| theme.parse().ok()))
+    }
+
+    fn set_theme(&mut self, table: &str, id: u64, theme: Option<Theme>) -> Result<()> {
+        let sql = format!(
+            "INSERT INTO {} (id, theme) VALUES (?1, ?2) ON CONFLICT (id) DO UPDATE SET theme = ?2",
+            table
+        );
+        self.conn
+            .execute(&sql, params![to_sql_id(id), theme.map(|theme| theme.to_string())])?;
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn query_alerts(&self, condition: &str, param: &dyn rusqlite::ToSql) -> Result<Vec<Alert>> {
+        let sql = format!(
+            "SELECT id, user_id, channel_id, symbol, direction, price, created_at FROM alerts WHERE {} ORDER BY id",
+            condition
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(&[param], alert_from_row)?;
+        rows.collect()
+    }
+}
+
+#[allow(dead_code)]
+fn alert_from_row(row: &Row<'_>) -> Result<Alert> {
+    let direction: String = row.get(4)?;
+    let direction = direction
+        .parse()
+        .map_err(|_| Error::InvalidColumnType(4, "direction".to_string(), rusqlite::types::Type::Text))?;
+
+    Ok(Alert {
+        id: row.get(0)?,
+        user_id: from_sql_id(row.get(1)?),
+        channel_id: from_sql_id(row.get(2)?),
+        symbol: row.get(3)?,
+        direction,
+        price: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+impl Store for SqliteStore {
+    fn guild_theme(&self, guild_id: u64) -> Result<Option<Theme>> {
+        self.theme("guilds", guild_id)
+    }
+
+    fn set_guild_theme(&mut self, guild_id: u64, theme: Option<Theme>) -> Result<()> {
+        self.set_theme("guilds", guild_id, theme)
+    }
+
+    fn user_theme(&self, user_id: u64) -> Result<Option<Theme>> {
+        self.theme("users", user_id)
+    }
+
+    fn set_user_theme(&mut self, user_id: u64, theme: Option<Theme>) -> Result<()> {
+        self.set_theme("users", user_id, theme)
+    }
+
+    fn watchlist(&self, user_id: u64) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT symbol FROM watchlists WHERE user_id = ? ORDER BY rowid")?;
+        let rows = stmt.query_map(params![to_sql_id(user_id)], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    fn watch(&mut self, user_id: u64, symbol: &str) -> Result<bool> {
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO watchlists (user_id, symbol) VALUES (?, ?)",
+            params![to_sql_id(user_id), symbol],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    fn unwatch(&mut self, user_id: u64, symbol: &str) -> Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM watchlists WHERE user_id = ? AND symbol = ?",
+            params![to_sql_id(user_id), symbol],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn alerts(&self, user_id: u64) -> Result<Vec<Alert>> {
+        self.query_alerts("user_id = ?", &to_sql_id(user_id))
+    }
+
+    fn alerts_for_symbol(&self, symbol: &str) -> Result<Vec<Alert>> {
+        self.query_alerts("symbol = ?", &symbol)
+    }
+
+    fn add_alert(&mut self, alert: &Alert) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO alerts (user_id, channel_id, symbol, direction, price, created_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                to_sql_id(alert.user_id),
+                to_sql_id(alert.channel_id),
+                alert.symbol,
+                alert.direction.to_string(),
+                alert.price,
+                alert.created_at,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    fn remove_alert(&mut self, user_id: u64, id: i64) -> Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM alerts WHERE user_id = ? AND id = ?",
+            params![to_sql_id(user_id), id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    fn positions(&self, user_id: u64) -> Result<Vec<Position>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT symbol, quantity, cost_basis FROM positions WHERE user_id = ? ORDER BY symbol")?;
+        let rows = stmt.query_map(params![to_sql_id(user_id)], |row| {
+            Ok(Position {
+                symbol: row.get(0)?,
+                quantity: row.get(1)?,
+                cost_basis: row.get(2)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    fn set_position(&mut self, user_id: u64, position: &Position) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO positions (user_id, symbol, quantity, cost_basis) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (user_id, symbol) DO UPDATE SET quantity = ?3, cost_basis = ?4",
+            params![to_sql_id(user_id), position.symbol, position.quantity, position.cost_basis],
+        )?;
+        Ok(())
+    }
+
+    fn remove_position(&mut self, user_id: u64, symbol: &str) -> Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM positions WHERE user_id = ? AND symbol = ?",
+            params![to_sql_id(user_id), symbol],
+        )?;
+        Ok(deleted > 0)
+    }
+}
+
+/// Keeps everything in memory, for tests.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    guild_themes: HashMap<u64, Theme>,
+    user_themes: HashMap<u64, Theme>,
+    watchlists: HashMap<u64, Vec<String>>,
+    alerts: Vec<Alert>,
+    next_alert_id: i64,
+    positions: HashMap<u64, Vec<Position>>,
+}
+
+#[cfg(test)]
+fn set_or_clear(themes: &mut HashMap<u64, Theme>, id: u64, theme: Option<Theme>) {
+    match theme {
+        Some(theme) => themes.insert(id, theme),
+        None => themes.remove(&id),
+    };
+}
+
+#[cfg(test)]
+impl Store for MemoryStore {
+    fn guild_theme(&self, guild_id: u64) -> Result<Option<Theme>> {
+        Ok(self.guild_themes.get(&guild_id).copied())
+    }
+
+    fn set_guild_theme(&mut self, guild_id: u64, theme: Option<Theme>) -> Result<()> {
+        set_or_clear(&mut self.guild_themes, guild_id, theme);
+        Ok(())
+    }
+
+    fn user_theme(&self, user_id: u64) -> Result<Option<Theme>> {
+        Ok(self.user_themes.get(&user_id).copied())
+    }
+
+    fn set_user_theme(&mut self, user_id: u64, theme: Option<Theme>) -> Result<()> {
+        set_or_clear(&mut self.user_themes, user_id, theme);
+        Ok(())
+    }
+
+    fn watchlist(&self, user_id: u64) -> Result<Vec<String>> {
+        Ok(self.watchlists.get(&user_id).cloned().unwrap_or_default())
+    }
+
+    fn watch(&mut self, user_id: u64, symbol: &str) -> Result<bool> {
+        let watchlist = self.watchlists.entry(user_id).or_default();
+        if watchlist.iter().any(|s| s == symbol) {
+            return Ok(false);
+        }
+        watchlist.push(symbol.to_string());
+        Ok(true)
+    }
+
+    fn unwatch(&mut self, user_id: u64, symbol: &str) -> Result<bool> {
+        let watchlist = self.watchlists.entry(user_id).or_default();
+        let len = watchlist.len();
+        watchlist.retain(|s| s != symbol);
+        Ok(watchlist.len() < len)
+    }
+
+    fn alerts(&self, user_id: u64) -> Result<Vec<Alert>> {
+        Ok(self.alerts.iter().filter(|a| a.user_id == user_id).cloned().collect())
+    }
+
+    fn alerts_for_symbol(&self, symbol: &str) -> Result<Vec<Alert>> {
+        Ok(self.alerts.iter().filter(|a| a.symbol == symbol).cloned().collect())
+    }
+
+    fn add_alert(&mut self, alert: &Alert) -> Result<i64> {
+        self.next_alert_id += 1;
+        self.alerts.push(Alert {
+            id: self.next_alert_id,
+            ..alert.clone()
+        });
+        Ok(self.next_alert_id)
+    }
+
+    fn remove_alert(&mut self, user_id: u64, id: i64) -> Result<bool> {
+        let len = self.alerts.len();
+        self.alerts.retain(|a| !(a.user_id == user_id && a.id == id));
+        Ok(self.alerts.len() < len)
+    }
+
+    fn positions(&self, user_id: u64) -> Result<Vec<Position>> {
+        let mut positions = self.positions.get(&user_id).cloned().unwrap_or_default();
+        positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        Ok(positions)
+    }
+
+    fn set_position(&mut self, user_id: u64, position: &Position) -> Result<()> {
+        let positions = self.positions.entry(user_id).or_default();
+        positions.retain(|p| p.symbol != position.symbol);
+        positions.push(position.clone());
+        Ok(())
+    }
+
+    fn remove_position(&mut self, user_id: u64, symbol: &str) -> Result<bool> {
+        let positions = self.positions.entry(user_id).or_default();
+        let len = positions.len();
+        positions.retain(|p| p.symbol != symbol);
+        Ok(positions.len() < len)
+    }
+}
+
+fn database_path() -> PathBuf {
+    env::var("STOUT_DB_PATH").unwrap_or_else(|_| "stout.db".to_string()).into()
+}
+
+/// Opens the database at `STOUT_DB_PATH` and brings it up to date. Called
+/// once at startup; the bot doesn't run without it, as everything it saves
+/// would be lost.
+pub fn open_from_env() -> Result<()> {
+    let path = database_path();
+    let store = SqliteStore::open(&path, &settings::settings_path()).map_err(|why| {
+        println!("Error opening database at {}: {:?}", path.display(), why);
+        why
+    })?;
+
+    if STORE.set(Mutex::new(Box::new(store))).is_err() {
+        println!("Database at {} was already open", path.display());
+    }
+    Ok(())
+}
+
+/// Runs `f` against the bot's store. SQLite calls block, so this happens
+/// off the async workers. A closure that panicked can't have left a write
+/// half done, as each is one statement, so the lock is taken back from it.
+pub async fn with_store<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut dyn Store) -> Result<T> + Send + 'static,
+{
+    let store = STORE.get().expect("the database is opened at startup");
+    task::spawn_blocking(move || f(store.lock().unwrap_or_else(PoisonError::into_inner).as_mut()))
+        .await
+        .unwrap_or_else(|why| panic::resume_unwind(why.into_panic()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn alert(user_id: u64, symbol: &str, direction: Direction, price: f64) -> Alert {
+        Alert {
+            id: 0,
+            user_id,
+            channel_id: 500,
+            symbol: symbol.to_string(),
+            direction,
+            price,
+            created_at: 1613745000,
+        }
+    }
+
+    /// The same checks run against both backends, so they can't drift apart.
+    fn exercise(store: &mut dyn Store) {
+        assert_eq!(store.guild_theme(1).unwrap(), None);
+        store.set_guild_theme(1, Some(Theme::Dark)).unwrap();
+        store.set_user_theme(2, Some(Theme::Colorblind)).unwrap();
+        store.set_user_theme(2, Some(Theme::ColorblindDark)).unwrap();
+        assert_eq!(store.guild_theme(1).unwrap(), Some(Theme::Dark));
+        assert_eq!(store.user_theme(2).unwrap(), Some(Theme::ColorblindDark));
+        store.set_guild_theme(1, None).unwrap();
+        assert_eq!(store.guild_theme(1).unwrap(), None);
+
+        assert!(store.watch(2, "TSLA").unwrap());
+        assert!(store.watch(2, "AAPL").unwrap());
+        assert!(!store.watch(2, "TSLA").unwrap());
+        assert!(store.watch(3, "SPY").unwrap());
+        assert_eq!(store.watchlist(2).unwrap(), ["TSLA", "AAPL"]);
+        assert!(store.unwatch(2, "TSLA").unwrap());
+        assert!(!store.unwatch(2, "TSLA").unwrap());
+        assert_eq!(store.watchlist(2).unwrap(), ["AAPL"]);
+
+        let above = store.add_alert(&alert(2, "AAPL", Direction::Above, 150.0)).unwrap();
+        let below = store.add_alert(&alert(3, "AAPL", Direction::Below, 120.0)).unwrap();
+        store.add_alert(&alert(2, "TSLA", Direction::Below, 600.0)).unwrap();
+        assert_ne!(above, below);
+        assert_eq!(
+            store.alerts_for_symbol("AAPL").unwrap(),
+            [
+                Alert { id: above, ..alert(2, "AAPL", Direction::Above, 150.0) },
+                Alert { id: below, ..alert(3, "AAPL", Direction::Below, 120.0) },
+            ]
+        );
+        assert!(!store.remove_alert(2, below).unwrap());
+        assert!(store.remove_alert(2, above).unwrap());
+        assert_eq!(store.alerts(2).unwrap().len(), 1);
+
+        let position = |symbol: &str, quantity, cost_basis| Position {
+            symbol: symbol.to_string(),
+            quantity,
+            cost_basis,
+        };
+        store.set_position(2, &position("TSLA", 10.0, 700.0)).unwrap();
+        store.set_position(2, &position("AAPL", 5.0, 130.0)).unwrap();
+        store.set_position(2, &position("TSLA", 15.0, 680.0)).unwrap();
+        assert_eq!(
+            store.positions(2).unwrap(),
+            [position("AAPL", 5.0, 130.0), position("TSLA", 15.0, 680.0)]
+        );
+        assert!(store.remove_position(2, "AAPL").unwrap());
+        assert!(!store.remove_position(2, "AAPL").unwrap());
+        assert!(store.positions(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_stores_state_in_memory() {
+        exercise(&mut MemoryStore::default());
+    }
+
+    #[test]
+    fn it_stores_state_in_sqlite() {
+        exercise(&mut SqliteStore::open_in_memory().unwrap());
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("stout-storage-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn it_migrates_once_and_keeps_state_across_opens() {
+        let path = temp_path("state.db");
+        let settings_path = temp_path("state-settings.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut store = SqliteStore::open(&path, &settings_path).unwrap();
+        store.set_user_theme(42, Some(Theme::Dark)).unwrap();
+        assert_eq!(store.migrate(&settings_path).unwrap(), 0);
+        drop(store);
+
+        let store = SqliteStore::open(&path, &settings_path).unwrap();
+        assert_eq!(store.user_theme(42).unwrap(), Some(Theme::Dark));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn it_imports_settings_until_it_succeeds() {
+        let path = temp_path("import.db");
+        let settings_path = temp_path("import-settings.txt");
+        let _ = fs::remove_file(&path);
+
+        // A directory can't be read as settings, so the import fails and is
+        // left to do.
+        fs::create_dir_all(&settings_path).unwrap();
+        assert!(SqliteStore::open(&path, &settings_path).is_err());
+        fs::remove_dir(&settings_path).unwrap();
+
+        fs::write(&settings_path, "user 42 dark").unwrap();
+        let mut store = SqliteStore::open(&path, &settings_path).unwrap();
+        assert_eq!(store.user_theme(42).unwrap(), Some(Theme::Dark));
+        store.set_user_theme(42, None).unwrap();
+        drop(store);
+
+        // Done once, it isn't repeated over later changes.
+        let store = SqliteStore::open(&path, &settings_path).unwrap();
+        assert_eq!(store.user_theme(42).unwrap(), None);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&settings_path).unwrap();
+    }
+
+    #[test]
+    fn it_triggers_alerts_on_crossing() {
+        assert!(alert(1, "SPY", Direction::Above, 400.0).is_triggered(400.0));
+        assert!(!alert(1, "SPY", Direction::Above, 400.0).is_triggered(399.9));
+        assert!(alert(1, "SPY", Direction::Below, 400.0).is_triggered(350.0));
+        assert_eq!("<".parse(), Ok(Direction::Below));
+    }
+}
@@ -0,0 +1,339 @@
+use crate::api::model::OptionsHeader;
+use crate::options::{self, ContractKind, CONTRACT_SIZE};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref QUANTITY_RE: Regex = Regex::new(r"^[+-]\d+$").unwrap();
+    static ref CONTRACT_RE: Regex = Regex::new(r"^(\d+(?:\.\d+)?)([cCpP])$").unwrap();
+    static ref DATE_RE: Regex = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+}
+
+pub const USAGE: &str = "Usage: `~payoff $SYMBOL +1 150c -1 160c [YYYY-MM-DD]`";
+
+/// Formats a profit or loss with its sign ahead of the dollar sign, e.g. `-$300.00`.
+pub fn format_pnl(value: f64) -> String {
+    let sign = if value < 0.0 { "-" } else { "" };
+    format!("{}${:.2}", sign, value.abs())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Leg {
+    /// Number of contracts, negative when short.
+    pub quantity: i64,
+    pub strike: f64,
+    pub kind: ContractKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyRequest {
+    pub legs: Vec<Leg>,
+    pub expiry: Option<String>,
+}
+
+/// Parses `+1 150c -1 160c 2024-06-21` style legs. The expiry is optional and
+/// may appear anywhere.
+pub fn parse_legs(tokens: &[&str]) -> Result<StrategyRequest, String> {
+    let mut legs = vec![];
+    let mut expiry = None;
+    let mut quantity = None;
+
+    for token in tokens {
+        if DATE_RE.is_match(token) {
+            expiry = Some(token.to_string());
+        } else if QUANTITY_RE.is_match(token) {
+            if quantity.is_some() {
+                return Err(format!("Expected a contract after quantity, found `{}`", token));
+            }
+            quantity = Some(token.parse::<i64>().map_err(|e| e.to_string())?);
+        } else if let Some(caps) = CONTRACT_RE.captures(token) {
+            let quantity = quantity
+                .take()
+                .ok_or_else(|| format!("Missing quantity for `{}`, e.g. `+1 {}`", token, token))?;
+            let kind = match &caps[2] {
+                "c" | "C" => ContractKind::Call,
+                _ => ContractKind::Put,
+            };
+            legs.push(Leg {
+                quantity,
+                strike: caps[1].parse().map_err(|_| format!("Invalid strike `{}`", token))?,
+                kind,
+            });
+        } else {
+            return Err(format!("Didn't understand `{}`", token));
+        }
+    }
+
+    if quantity.is_some() {
+        return Err("Expected a contract after the last quantity".to_string());
+    }
+    if legs.is_empty() || legs.iter().any(|l| l.quantity == 0) {
+        return Err(USAGE.to_string());
+    }
+
+    Ok(StrategyRequest { legs, expiry })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PricedLeg {
+    pub leg: Leg,
+    /// Per-share price paid (or received, when short) for the contract.
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strategy {
+    pub expiration_date: i64,
+    pub legs: Vec<PricedLeg>,
+}
+
+impl Strategy {
+    /// Prices `legs` from the chain in `header`, failing on any strike that
+    /// isn't listed.
+    pub fn price(header: &OptionsHeader, legs: &[Leg]) -> Result<Strategy, String> {
+        let data = header.options.first().ok_or("No options listed for that expiration")?;
+
+        let legs = legs
+            .iter()
+            .map(|leg| {
+                let contracts = match leg.kind {
+                    ContractKind::Call => &data.calls,
+                    ContractKind::Put => &data.puts,
+                };
+                contracts
+                    .iter()
+                    .find(|c| (c.strike - leg.strike).abs() < 1e-6)
+                    .map(|c| PricedLeg {
+                        leg: *leg,
+                        price: options::contract_price(c),
+                    })
+                    .ok_or_else(|| format!("No {}{} listed", leg.strike, leg.kind))
+            })
+            .collect::<Result<Vec<PricedLeg>, String>>()?;
+
+        Ok(Strategy {
+            expiration_date: data.expiration_date,
+            legs,
+        })
+    }
+
+    /// Cost to open the position; negative for a net credit.
+    pub fn net_debit(&self) -> f64 {
+        self.legs
+            .iter()
+            .map(|l| l.leg.quantity as f64 * l.price * CONTRACT_SIZE)
+            .sum()
+    }
+
+    /// Profit or loss at expiry with the underlying at `price`.
+    pub fn payoff(&self, price: f64) -> f64 {
+        let intrinsic = self
+            .legs
+            .iter()
+            .map(|l| {
+                let value = match l.leg.kind {
+                    ContractKind::Call => (price - l.leg.strike).max(0.0),
+                    ContractKind::Put => (l.leg.strike - price).max(0.0),
+                };
+                l.leg.quantity as f64 * value * CONTRACT_SIZE
+            })
+            .sum::<f64>();
+
+        intrinsic - self.net_debit()
+    }
+
+    /// Strikes in ascending order, where the payoff changes slope.
+    pub fn strikes(&self) -> Vec<f64> {
+        let mut strikes = self.legs.iter().map(|l| l.leg.strike).collect::<Vec<f64>>();
+        strikes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        strikes.dedup();
+        strikes
+    }
+
+    /// Slope of the payoff above the highest strike, where only calls matter.
+    fn upside_slope(&self) -> f64 {
+        self.legs
+            .iter()
+            .filter(|l| l.leg.kind == ContractKind::Call)
+            .map(|l| l.leg.quantity as f64 * CONTRACT_SIZE)
+            .sum()
+    }
+
+    /// Underlying prices at which the position breaks even at expiry.
+    pub fn breakevens(&self) -> Vec<f64> {
+        let mut points = vec![0.0];
+        points.extend(self.strikes());
+
+        let mut breakevens = vec![];
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let (pa, pb) = (self.payoff(a), self.payoff(b));
+
+            if pa == 0.0 {
+                breakevens.push(a);
+            } else if pa.signum() != pb.signum() && pb != 0.0 {
+                breakevens.push(a + (b - a) * pa / (pa - pb));
+            }
+        }
+
+        let last = *points.last().unwrap();
+        let payoff = self.payoff(last);
+        let slope = self.upside_slope();
+        if payoff == 0.0 {
+            breakevens.push(last);
+        } else if slope != 0.0 && payoff.signum() != slope.signum() {
+            breakevens.push(last - payoff / slope);
+        }
+
+        breakevens
+    }
+
+    /// Best outcome at expiry, or `None` if it's unlimited.
+    pub fn max_profit(&self) -> Option<f64> {
+        if self.upside_slope() > 0.0 {
+            return None;
+        }
+
+        self.extremes().fold(None, |max, v| Some(max.map_or(v, |m: f64| m.max(v))))
+    }
+
+    /// Worst outcome at expiry, or `None` if it's unlimited.
+    pub fn max_loss(&self) -> Option<f64> {
+        if self.upside_slope() < 0.0 {
+            return None;
+        }
+
+        self.extremes().fold(None, |min, v| Some(min.map_or(v, |m: f64| m.min(v))))
+    }
+
+    /// The payoff at zero and at each strike; with a bounded upside the
+    /// extremes are always among these.
+    fn extremes(&self) -> impl Iterator<Item = f64> + '_ {
+        std::iter::once(0.0)
+            .chain(self.strikes())
+            .map(move |price| self.payoff(price))
+    }
+}
+
+/// Looks up the requested expiration, or the nearest one, and prices the legs
+/// from its chain. Returns the strategy with the current underlying price.
+pub async fn fetch_strategy(symbol: &str, request: &StrategyRequest) -> Result<(Strategy, f64), String> {
+    let unavailable = |why| {
+        println!("Error fetching options for {}: {:?}", symbol, why);
+        format!("Couldn't get options data for ${}", symbol)
+    };
+
+    let expiration_dates = crate::CLIENT
+        .get_options_expiration_dates(symbol)
+        .await
+        .map_err(unavailable)?;
+
+    let expiration_date = match &request.expiry {
+        Some(expiry) => expiration_dates
+            .iter()
+            .copied()
+            .find(|d| &options::format_expiration(*d) == expiry)
+            .ok_or_else(|| format!("No ${} options expiring {}", symbol, expiry))?,
+        None => expiration_dates
+            .iter()
+            .copied()
+            .min()
+            .ok_or_else(|| format!("No listed options for ${}", symbol))?,
+    };
+
+    let header = crate::CLIENT
+        .get_options_for_expiration_date(symbol, expiration_date)
+        .await
+        .map_err(unavailable)?;
+
+    let strategy = Strategy::price(&header, &request.legs)?;
+    Ok((strategy, header.quote.regular_market_price))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strategy(legs: &[(i64, f64, ContractKind, f64)]) -> Strategy {
+        Strategy {
+            expiration_date: 1616112000,
+            legs: legs
+                .iter()
+                .map(|(quantity, strike, kind, price)| PricedLeg {
+                    leg: Leg {
+                        quantity: *quantity,
+                        strike: *strike,
+                        kind: *kind,
+                    },
+                    price: *price,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn it_parses_legs() {
+        let request = parse_legs(&["+1", "150c", "-2", "160.5C", "2024-06-21", "+1", "140p"]).unwrap();
+        assert_eq!(request.expiry, Some("2024-06-21".to_string()));
+        assert_eq!(
+            request.legs,
+            [
+                Leg { quantity: 1, strike: 150.0, kind: ContractKind::Call },
+                Leg { quantity: -2, strike: 160.5, kind: ContractKind::Call },
+                Leg { quantity: 1, strike: 140.0, kind: ContractKind::Put },
+            ]
+        );
+
+        assert_eq!(parse_legs(&["+1", "150c"]).unwrap().expiry, None);
+        assert!(parse_legs(&["150c"]).is_err());
+        assert!(parse_legs(&["+1", "+1", "150c"]).is_err());
+        assert!(parse_legs(&["+1", "150x"]).is_err());
+        assert!(parse_legs(&["+1"]).is_err());
+        assert!(parse_legs(&[]).is_err());
+    }
+
+    #[test]
+    fn it_prices_legs_from_the_chain() {
+        let header = options::fixture(include_str!("../fixtures/options_spy.json"));
+        let legs = parse_legs(&["+1", "390c", "-1", "395c"]).unwrap().legs;
+
+        let strategy = Strategy::price(&header, &legs).unwrap();
+        assert_eq!(strategy.expiration_date, 1616112000);
+        assert!((strategy.net_debit() - 240.0).abs() < 1e-6);
+
+        let legs = parse_legs(&["+1", "391c"]).unwrap().legs;
+        assert!(Strategy::price(&header, &legs).is_err());
+    }
+
+    #[test]
+    fn it_analyzes_a_bull_call_spread() {
+        let spread = strategy(&[
+            (1, 150.0, ContractKind::Call, 5.0),
+            (-1, 160.0, ContractKind::Call, 2.0),
+        ]);
+
+        assert_eq!(spread.payoff(140.0), -300.0);
+        assert_eq!(spread.payoff(170.0), 700.0);
+        assert_eq!(spread.breakevens(), [153.0]);
+        assert_eq!(spread.max_profit(), Some(700.0));
+        assert_eq!(spread.max_loss(), Some(-300.0));
+        assert_eq!(format_pnl(-300.0), "-$300.00");
+        assert_eq!(format_pnl(700.0), "$700.00");
+    }
+
+    #[test]
+    fn it_analyzes_unlimited_positions() {
+        let call = strategy(&[(1, 100.0, ContractKind::Call, 4.0)]);
+        assert_eq!(call.breakevens(), [104.0]);
+        assert_eq!(call.max_profit(), None);
+        assert_eq!(call.max_loss(), Some(-400.0));
+
+        let straddle = strategy(&[
+            (-1, 100.0, ContractKind::Call, 4.0),
+            (-1, 100.0, ContractKind::Put, 3.0),
+        ]);
+        assert_eq!(straddle.breakevens(), [93.0, 107.0]);
+        assert_eq!(straddle.max_profit(), Some(700.0));
+        assert_eq!(straddle.max_loss(), None);
+    }
+}
@@ -0,0 +1,162 @@
+use lazy_static::lazy_static;
+use std::env;
+
+lazy_static! {
+    /// Aliases set in `SYMBOL_ALIASES`, read once at startup.
+    static ref CONFIGURED_ALIASES: Vec<(String, String)> = env::var("SYMBOL_ALIASES")
+        .map(|s| parse_aliases(&s))
+        .unwrap_or_default();
+}
+
+/// Friendlier names for symbols Yahoo spells differently. `SYMBOL_ALIASES`
+/// can add to or override these.
+const DEFAULT_ALIASES: [(&str, &str); 9] = [
+    ("BTC", "BTC-USD"),
+    ("ETH", "ETH-USD"),
+    ("DOGE", "DOGE-USD"),
+    ("SPX", "^GSPC"),
+    ("NDX", "^NDX"),
+    ("DJI", "^DJI"),
+    ("DJIA", "^DJI"),
+    ("RUT", "^RUT"),
+    ("VIX", "^VIX"),
+];
+
+/// What kind of instrument a symbol is, which decides what its embed shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instrument {
+    Equity,
+    Etf,
+    Fund,
+    Crypto,
+    Future,
+    Currency,
+    Index,
+}
+
+impl Instrument {
+    /// Reads Yahoo's quote type, e.g. `CRYPTOCURRENCY`, treating anything
+    /// unrecognized as an equity.
+    pub fn from_quote_type(quote_type: &str) -> Instrument {
+        match quote_type.to_uppercase().as_str() {
+            "ETF" => Instrument::Etf,
+            "MUTUALFUND" => Instrument::Fund,
+            "CRYPTOCURRENCY" => Instrument::Crypto,
+            "FUTURE" => Instrument::Future,
+            "CURRENCY" => Instrument::Currency,
+            "INDEX" => Instrument::Index,
+            _ => Instrument::Equity,
+        }
+    }
+
+    /// Whether a market cap means anything for the instrument.
+    pub fn has_market_cap(self) -> bool {
+        matches!(self, Instrument::Equity | Instrument::Crypto)
+    }
+
+    /// Whether StockTwits has a stream for the symbol as Yahoo spells it.
+    pub fn on_stocktwits(self) -> bool {
+        matches!(self, Instrument::Equity | Instrument::Etf | Instrument::Fund)
+    }
+}
+
+/// A futures contract's details, shown in place of a market cap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contract {
+    pub name: String,
+    pub expires: Option<String>,
+    pub open_interest: Option<f64>,
+}
+
+/// Reads aliases written as `ALIAS=SYMBOL,ALIAS=SYMBOL`, e.g.
+/// `BTC=BTC-USD,SPX=^GSPC`, skipping any missing either side.
+pub fn parse_aliases(s: &str) -> Vec<(String, String)> {
+    s.split(',')
+        .filter_map(|entry| {
+            let (alias, symbol) = entry.split_once('=')?;
+            let alias = alias.trim().trim_start_matches('$').to_uppercase();
+            let symbol = symbol.trim().to_uppercase();
+
+            if alias.is_empty() || symbol.is_empty() {
+                None
+            } else {
+                Some((alias, symbol))
+            }
+        })
+        .collect()
+}
+
+/// The symbol Yahoo knows `symbol` by, going through the aliases set in
+/// `SYMBOL_ALIASES` and then the built-in ones.
+pub fn resolve(symbol: &str) -> String {
+    resolve_with(symbol, &CONFIGURED_ALIASES)
+}
+
+fn resolve_with(symbol: &str, configured: &[(String, String)]) -> String {
+    let symbol = symbol.trim_start_matches('$').to_uppercase();
+    configured
+        .iter()
+        .map(|(alias, symbol)| (alias.as_str(), symbol.as_str()))
+        .chain(DEFAULT_ALIASES.iter().copied())
+        .find(|(alias, _)| *alias == symbol)
+        .map_or(symbol.clone(), |(_, resolved)| resolved.to_string())
+}
+
+/// Formats a price the way the instrument is quoted: exchange rates to four
+/// places and index levels in points, everything else in its currency.
+/// Prices under a dollar get four places too.
+pub fn format_price(price: f64, instrument: Instrument, currency: Option<&str>) -> String {
+    let decimals = if instrument == Instrument::Currency || price.abs() < 1.0 { 4 } else { 2 };
+
+    match (instrument, currency) {
+        (Instrument::Currency, _) | (Instrument::Index, _) => format!("{:.*}", decimals, price),
+        (_, None) | (_, Some("USD")) => format!("${:.*}", decimals, price),
+        (_, Some(currency)) => format!("{:.*} {}", decimals, price, currency),
+    }
+}
+
+pub fn yahoo_url(symbol: &str) -> String {
+    format!(
+        "https://finance.yahoo.com/quote/{}/",
+        symbol.replace('^', "%5E").replace('=', "%3D")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_resolves_aliases() {
+        let configured = parse_aliases("btc=BTC-CAD, GOLD = gc=f ,=AAPL,NOPE=");
+        assert_eq!(
+            configured,
+            [
+                ("BTC".to_string(), "BTC-CAD".to_string()),
+                ("GOLD".to_string(), "GC=F".to_string()),
+            ]
+        );
+
+        assert_eq!(resolve_with("$spx", &configured), "^GSPC");
+        assert_eq!(resolve_with("BTC", &configured), "BTC-CAD");
+        assert_eq!(resolve_with("BTC", &[]), "BTC-USD");
+        assert_eq!(resolve_with("GOLD", &configured), "GC=F");
+        assert_eq!(resolve_with("aapl", &configured), "AAPL");
+    }
+
+    #[test]
+    fn it_formats_prices_by_instrument() {
+        assert_eq!(format_price(132.051, Instrument::Equity, Some("USD")), "$132.05");
+        assert_eq!(format_price(81.5, Instrument::Equity, Some("CAD")), "81.50 CAD");
+        assert_eq!(format_price(1.21434, Instrument::Currency, Some("USD")), "1.2143");
+        assert_eq!(format_price(3913.1, Instrument::Index, Some("USD")), "3913.10");
+        assert_eq!(format_price(0.05812, Instrument::Crypto, None), "$0.0581");
+    }
+
+    #[test]
+    fn it_escapes_yahoo_urls() {
+        assert_eq!(yahoo_url("^GSPC"), "https://finance.yahoo.com/quote/%5EGSPC/");
+        assert_eq!(yahoo_url("ES=F"), "https://finance.yahoo.com/quote/ES%3DF/");
+        assert_eq!(yahoo_url("RY.TO"), "https://finance.yahoo.com/quote/RY.TO/");
+    }
+}
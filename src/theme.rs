@@ -0,0 +1,156 @@
+use plotters::style::{IntoFont, RGBColor, TextStyle};
+use std::fmt;
+use std::str::FromStr;
+
+/// Colors used by every chart the bot draws.
+pub struct Palette {
+    pub background: RGBColor,
+    /// Text, axes, and reference lines.
+    pub foreground: RGBColor,
+    pub grid: RGBColor,
+    pub up: RGBColor,
+    pub down: RGBColor,
+    /// One color per overlay or compared symbol, in order.
+    pub series: [RGBColor; 5],
+}
+
+impl Palette {
+    pub fn font(&self, size: u32) -> TextStyle<'static> {
+        ("sans-serif", size).into_font().color(&self.foreground)
+    }
+
+    /// Color for the `n`th of several series, cycling if there are too many.
+    pub fn series(&self, n: usize) -> &RGBColor {
+        &self.series[n % self.series.len()]
+    }
+
+    pub fn change(&self, change: f64) -> &RGBColor {
+        if change >= 0.0 {
+            &self.up
+        } else {
+            &self.down
+        }
+    }
+}
+
+const LIGHT: Palette = Palette {
+    background: RGBColor(255, 255, 255),
+    foreground: RGBColor(0, 0, 0),
+    grid: RGBColor(220, 220, 220),
+    up: RGBColor(0, 160, 90),
+    down: RGBColor(210, 40, 40),
+    series: [
+        RGBColor(30, 100, 220),
+        RGBColor(240, 140, 0),
+        RGBColor(150, 60, 200),
+        RGBColor(0, 170, 190),
+        RGBColor(120, 120, 120),
+    ],
+};
+
+/// Matches the Discord dark mode chat background, so charts don't glare.
+const DARK: Palette = Palette {
+    background: RGBColor(54, 57, 63),
+    foreground: RGBColor(220, 221, 222),
+    grid: RGBColor(79, 84, 92),
+    up: RGBColor(59, 165, 93),
+    down: RGBColor(237, 66, 69),
+    series: [
+        RGBColor(88, 101, 242),
+        RGBColor(250, 166, 26),
+        RGBColor(235, 69, 158),
+        RGBColor(0, 190, 210),
+        RGBColor(160, 160, 160),
+    ],
+};
+
+// Up and down from the Okabe-Ito palette, which stay distinct under the
+// common forms of color blindness.
+const COLORBLIND: Palette = Palette {
+    up: RGBColor(0, 114, 178),
+    down: RGBColor(213, 94, 0),
+    series: [
+        RGBColor(230, 159, 0),
+        RGBColor(86, 180, 233),
+        RGBColor(204, 121, 167),
+        RGBColor(0, 158, 115),
+        RGBColor(120, 120, 120),
+    ],
+    ..LIGHT
+};
+
+const COLORBLIND_DARK: Palette = Palette {
+    up: RGBColor(86, 180, 233),
+    down: RGBColor(230, 159, 0),
+    series: [
+        RGBColor(240, 228, 66),
+        RGBColor(204, 121, 167),
+        RGBColor(0, 158, 115),
+        RGBColor(213, 94, 0),
+        RGBColor(160, 160, 160),
+    ],
+    ..DARK
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+    Colorblind,
+    ColorblindDark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 4] = [Theme::Light, Theme::Dark, Theme::Colorblind, Theme::ColorblindDark];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::Colorblind => "colorblind",
+            Theme::ColorblindDark => "colorblind-dark",
+        }
+    }
+
+    pub fn palette(self) -> &'static Palette {
+        match self {
+            Theme::Light => &LIGHT,
+            Theme::Dark => &DARK,
+            Theme::Colorblind => &COLORBLIND,
+            Theme::ColorblindDark => &COLORBLIND_DARK,
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Theme {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Theme::ALL
+            .iter()
+            .copied()
+            .find(|t| t.as_str() == s.to_lowercase())
+            .ok_or("Valid themes are: 'light', 'dark', 'colorblind', 'colorblind-dark'")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_theme_names() {
+        for theme in Theme::ALL.iter() {
+            assert_eq!(theme.as_str().parse(), Ok(*theme));
+        }
+        assert_eq!("Dark".parse(), Ok(Theme::Dark));
+        assert!("neon".parse::<Theme>().is_err());
+    }
+}